@@ -1,21 +1,196 @@
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
+
 use crate::{
-    parser,
-    shell::{CommandInput, CommandOutput},
+    os, parser,
+    shell::{self, CommandInput, CommandOutput},
 };
 
+const USAGE: &str = "cd: usage: cd [-L|-P] [dir]";
+
+/// `cd [-L|-P] [dir]`: with `-L`, the default, `..` removes the previous component of the path
+/// as typed, so leaving a symlinked directory goes back where it came from; `-P` resolves
+/// symlinks and keeps the physical path.
 pub fn cd(input: CommandInput) -> CommandOutput {
-    let Some(path) = input.command_arguments.first() else {
-        return CommandOutput::empty();
+    let mut physical = false;
+    let mut operands = input.command_arguments;
+    while let [option, rest @ ..] = operands {
+        if option == "--" {
+            operands = rest;
+            break;
+        }
+        let Some(flags) = option.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => {
+                    return CommandOutput::failure(format!("cd: -{flag}: invalid option\n{USAGE}"));
+                }
+            }
+        }
+        operands = rest;
+    }
+
+    let path = match operands {
+        [] => match env::var("HOME").ok().filter(|home| !home.is_empty()) {
+            Some(home) => home,
+            None => return CommandOutput::failure(String::from("cd: HOME not set")),
+        },
+        [path] if path == "-" => match env::var("OLDPWD").ok().filter(|old| !old.is_empty()) {
+            Some(old) => {
+                // like bash, `cd -` prints the directory it switched to
+                let mut result = change_dir(&old, &input, physical);
+                if let Some(dir) = &result.updated_dir {
+                    result.std_output = Some(format!("{}\n", dir.display()));
+                }
+                return result;
+            }
+            None => return CommandOutput::failure(String::from("cd: OLDPWD not set")),
+        },
+        [path] => path.clone(),
+        _ => return CommandOutput::failure(String::from("cd: too many arguments")),
+    };
+
+    change_dir(&path, &input, physical)
+}
+
+fn change_dir(path: &str, input: &CommandInput, physical: bool) -> CommandOutput {
+    // a directory found through a `CDPATH` entry is printed, since it isn't where `path` points
+    let (base, mut print) = match search_cdpath(path, input.current_dir) {
+        Some(base) => (base, true),
+        None => (input.current_dir.to_path_buf(), false),
+    };
+
+    let target_dir = if physical && !path.starts_with('~') {
+        // `..` is left to the file system, which resolves it after following symlinks
+        base.join(path)
+    } else {
+        match parser::parse_path(path, &base) {
+            Ok(target_dir) => target_dir,
+            Err(message) => return CommandOutput::failure(message),
+        }
+    };
+
+    let target_dir = match os::check_directory(&target_dir) {
+        Ok(()) => target_dir,
+        Err(error)
+            if error.kind() == ErrorKind::NotFound
+                && shell::option_enabled("cdspell")
+                && let Some(corrected) = correct_spelling(&target_dir) =>
+        {
+            print = true;
+            corrected
+        }
+        Err(error) => {
+            return CommandOutput::failure(format!("cd: {path}: {}", os::describe_error(&error)));
+        }
     };
 
-    match parser::parse_path(path, input.current_dir) {
-        Ok(target_dir) => {
-            if target_dir.exists() {
-                CommandOutput::path_update(target_dir)
-            } else {
-                CommandOutput::failure(format!("cd: {path}: No such file or directory"))
+    let target_dir = if physical {
+        match target_dir.canonicalize() {
+            Ok(target_dir) => target_dir,
+            Err(error) => {
+                return CommandOutput::failure(format!(
+                    "cd: {path}: {}",
+                    os::describe_error(&error)
+                ));
             }
         }
-        Err(message) => CommandOutput::failure(message),
+    } else {
+        target_dir
+    };
+
+    let mut result = CommandOutput::path_update(target_dir.clone());
+    if print {
+        result.std_output = Some(format!("{}\n", target_dir.display()));
+    }
+    result
+}
+
+/// Looks a relative `path` up in the colon-separated `CDPATH` and returns the entry it was found
+/// in. An empty entry stands for the current directory: a match there returns `None` so the
+/// plain, unprinted `cd` takes over.
+fn search_cdpath(path: &str, current_dir: &Path) -> Option<PathBuf> {
+    let first = Path::new(path).components().next()?;
+    if !matches!(first, Component::Normal(_)) || path.starts_with('~') {
+        return None;
+    }
+
+    let cdpath = env::var("CDPATH").ok()?;
+    for entry in cdpath.split(':') {
+        if entry.is_empty() {
+            if current_dir.join(path).is_dir() {
+                return None;
+            }
+            continue;
+        }
+        if let Ok(base) = parser::parse_path(entry, current_dir)
+            && base.join(path).is_dir()
+        {
+            return Some(base);
+        }
+    }
+    None
+}
+
+/// `cdspell`: rebuilds `target_dir` component by component, replacing each missing one with
+/// the closest directory name, as long as it's only off by a transposed, missing or extra
+/// character.
+fn correct_spelling(target_dir: &Path) -> Option<PathBuf> {
+    let mut corrected = PathBuf::new();
+    for component in target_dir.components() {
+        let Component::Normal(name) = component else {
+            corrected.push(component);
+            continue;
+        };
+        if corrected.join(name).is_dir() {
+            corrected.push(name);
+            continue;
+        }
+
+        let name = name.to_string_lossy();
+        let mut candidates: Vec<String> = fs::read_dir(&corrected)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|candidate| is_misspelling(&name, candidate))
+            .collect();
+        candidates.sort();
+        corrected.push(candidates.first()?);
+    }
+    Some(corrected)
+}
+
+fn is_misspelling(typed: &str, candidate: &str) -> bool {
+    let typed: Vec<char> = typed.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    // skip the common prefix, then the rest must line up after one edit
+    let common = typed
+        .iter()
+        .zip(&candidate)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (typed, candidate) = (&typed[common..], &candidate[common..]);
+
+    match typed.len() as isize - candidate.len() as isize {
+        // transposed characters
+        0 => {
+            typed.len() >= 2
+                && typed[0] == candidate[1]
+                && typed[1] == candidate[0]
+                && typed[2..] == candidate[2..]
+        }
+        // a missing character
+        -1 => typed == &candidate[1..],
+        // one character too many
+        1 => &typed[1..] == candidate,
+        _ => false,
     }
 }
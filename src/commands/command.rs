@@ -0,0 +1,67 @@
+use crate::{
+    commands::resolve,
+    shell::{CommandInput, CommandOutput},
+};
+
+const USAGE: &str = "command: usage: command [-vV] command [arg ...]";
+
+/// `command -v name...` prints what each name runs as (the name of a builtin or the path of a
+/// file), `command -V` describes it like `type`. Plain `command name args` is run by the main
+/// loop as `name args`.
+pub fn command(input: CommandInput) -> CommandOutput {
+    let mut short = false;
+    let mut verbose = false;
+
+    let mut names = input.command_arguments;
+    while let [option, rest @ ..] = names {
+        if option == "--" {
+            names = rest;
+            break;
+        }
+        let Some(flags) = option.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'v' => short = true,
+                'V' => verbose = true,
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "command: -{flag}: invalid option\n{USAGE}"
+                    ));
+                }
+            }
+        }
+        names = rest;
+    }
+
+    if !short && !verbose {
+        return CommandOutput::empty();
+    }
+
+    let mut output = String::new();
+    let mut errors = Vec::new();
+    let mut found_all = true;
+
+    for name in names {
+        let Some(resolution) = resolve::resolve(name, &input, false, false)
+            .into_iter()
+            .next()
+        else {
+            found_all = false;
+            if verbose {
+                errors.push(format!("command: {name}: not found"));
+            }
+            continue;
+        };
+
+        let line = match (resolution.path(), verbose) {
+            (_, true) => resolution.describe(name),
+            (Some(path), false) => path.display().to_string(),
+            (None, false) => name.clone(),
+        };
+        output += format!("{line}\n").as_str();
+    }
+
+    resolve::report(output, errors, found_all)
+}
@@ -0,0 +1,103 @@
+use crate::shell::{
+    CommandInput, CommandOutput,
+    completion::{CompletionRegistry, CompletionSpec},
+};
+
+pub fn complete(input: CommandInput) -> CommandOutput {
+    let mut spec = CompletionSpec::default();
+    let mut names: Vec<&str> = Vec::new();
+    let mut print = false;
+    let mut remove = false;
+
+    let mut arguments = input.command_arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let Some(flags) = argument.strip_prefix('-') else {
+            names.push(argument);
+            continue;
+        };
+
+        for flag in flags.chars() {
+            match flag {
+                'p' => print = true,
+                'r' => remove = true,
+                'd' => spec.directories = true,
+                'f' => spec.files = true,
+                'c' => spec.commands = true,
+                'W' | 'C' | 'F' => {
+                    let Some(value) = arguments.next() else {
+                        return CommandOutput::failure(format!(
+                            "complete: -{flag}: option requires an argument"
+                        ));
+                    };
+
+                    match flag {
+                        'W' => spec.words = value.split_whitespace().map(String::from).collect(),
+                        'C' => spec.command = Some(value.clone()),
+                        _ => spec.function = Some(value.clone()),
+                    }
+                }
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "complete: -{flag}: invalid option\ncomplete: usage: complete [-pr] [-dfc] [-W wordlist] [-F function] [-C command] [name ...]"
+                    ));
+                }
+            }
+        }
+    }
+
+    if remove {
+        let mut registry = input.completions.clone();
+        if names.is_empty() {
+            registry.clear();
+        }
+        // every name that has a spec is removed, even when some of the others don't
+        let errors: Vec<String> = names
+            .into_iter()
+            .filter(|name| registry.remove(name).is_none())
+            .map(|name| format!("complete: {name}: no completion specification"))
+            .collect();
+        return CommandOutput {
+            std_error: (!errors.is_empty()).then(|| errors.join("\n")),
+            completions: Some(registry),
+            ..Default::default()
+        };
+    }
+
+    if print || spec == CompletionSpec::default() {
+        return print_specs(input.completions, &names);
+    }
+
+    if names.is_empty() {
+        return CommandOutput::failure(
+            "complete: usage: complete [-pr] [-dfc] [-W wordlist] [-F function] [-C command] [name ...]".to_string(),
+        );
+    }
+
+    let mut registry = input.completions.clone();
+    for name in names {
+        registry.insert(name, spec.clone());
+    }
+    CommandOutput::completions_update(registry)
+}
+
+fn print_specs(registry: &CompletionRegistry, names: &[&str]) -> CommandOutput {
+    let mut output = String::new();
+
+    if names.is_empty() {
+        for (name, spec) in registry.entries() {
+            output += format!("{}\n", spec.describe(name)).as_str();
+        }
+        return CommandOutput::success(output);
+    }
+
+    for name in names {
+        match registry.get(name) {
+            Some(spec) => output += format!("{}\n", spec.describe(name)).as_str(),
+            None => {
+                return CommandOutput::failure(format!("complete: {name}: no completion specification"));
+            }
+        }
+    }
+
+    CommandOutput::success(output)
+}
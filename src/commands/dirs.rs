@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    os, parser,
+    shell::{CommandInput, CommandOutput, prompt},
+};
+
+const DIRS_USAGE: &str = "dirs: usage: dirs [-clpv] [+N] [-N]";
+const PUSHD_USAGE: &str = "pushd: usage: pushd [dir | +N | -N]";
+const POPD_USAGE: &str = "popd: usage: popd [+N | -N]";
+
+/// `dirs`: the current directory followed by the `pushd` stack.
+pub fn dirs(input: CommandInput) -> CommandOutput {
+    let mut clear = false;
+    let mut long = false;
+    let mut per_line = false;
+    let mut numbered = false;
+    let mut index: Option<&str> = None;
+
+    for argument in input.command_arguments {
+        if stack_index(argument).is_some() {
+            index = Some(argument);
+            continue;
+        }
+        let Some(flags) = argument.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            return CommandOutput::failure(format!(
+                "dirs: {argument}: invalid argument\n{DIRS_USAGE}"
+            ));
+        };
+        for flag in flags.chars() {
+            match flag {
+                'c' => clear = true,
+                'l' => long = true,
+                'p' => per_line = true,
+                'v' => numbered = true,
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "dirs: -{flag}: invalid option\n{DIRS_USAGE}"
+                    ));
+                }
+            }
+        }
+    }
+
+    if clear {
+        return CommandOutput {
+            dir_stack: Some(Vec::new()),
+            ..Default::default()
+        };
+    }
+
+    let entries = full_stack(input.current_dir, input.dir_stack);
+
+    if let Some(spec) = index {
+        let Some(position) = resolve_index(spec, entries.len()) else {
+            return CommandOutput::failure(format!(
+                "dirs: {spec}: directory stack index out of range"
+            ));
+        };
+        return CommandOutput::success(format!("{}\n", show(&entries[position], long)));
+    }
+
+    let output = if numbered {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(position, dir)| format!("{position:>2}  {}\n", show(dir, long)))
+            .collect()
+    } else if per_line {
+        entries
+            .iter()
+            .map(|dir| format!("{}\n", show(dir, long)))
+            .collect()
+    } else {
+        listing(&entries, long)
+    };
+    CommandOutput::success(output)
+}
+
+/// `pushd dir` saves the current directory on the stack and changes to `dir`, `pushd +N`/`-N`
+/// rotates the stack so that entry becomes the current directory, and plain `pushd` swaps the
+/// top two entries.
+pub fn pushd(input: CommandInput) -> CommandOutput {
+    let mut entries = full_stack(input.current_dir, input.dir_stack);
+
+    match input.command_arguments {
+        [] => {
+            if entries.len() < 2 {
+                return CommandOutput::failure(String::from("pushd: no other directory"));
+            }
+            entries.swap(0, 1);
+        }
+        [spec] if stack_index(spec).is_some() => {
+            let Some(position) = resolve_index(spec, entries.len()) else {
+                return CommandOutput::failure(format!(
+                    "pushd: {spec}: directory stack index out of range"
+                ));
+            };
+            entries.rotate_left(position);
+        }
+        [path] if path.starts_with('-') && path.len() > 1 => {
+            return CommandOutput::failure(format!("pushd: {path}: invalid option\n{PUSHD_USAGE}"));
+        }
+        [path] => match parser::parse_path(path, input.current_dir) {
+            Ok(target_dir) => match os::check_directory(&target_dir) {
+                Ok(()) => entries.insert(0, target_dir),
+                Err(error) => {
+                    return CommandOutput::failure(format!(
+                        "pushd: {path}: {}",
+                        os::describe_error(&error)
+                    ));
+                }
+            },
+            Err(message) => return CommandOutput::failure(message),
+        },
+        _ => return CommandOutput::failure(String::from("pushd: too many arguments")),
+    }
+
+    change_to_top("pushd", entries)
+}
+
+/// `popd` drops the top of the stack and changes to the new top; `popd +N`/`-N` removes that
+/// entry instead, only changing directory when it's the current one.
+pub fn popd(input: CommandInput) -> CommandOutput {
+    if input.dir_stack.is_empty() {
+        return CommandOutput::failure(String::from("popd: directory stack empty"));
+    }
+    let mut entries = full_stack(input.current_dir, input.dir_stack);
+
+    let position = match input.command_arguments {
+        [] => 0,
+        [spec] if stack_index(spec).is_some() => match resolve_index(spec, entries.len()) {
+            Some(position) => position,
+            None => {
+                return CommandOutput::failure(format!(
+                    "popd: {spec}: directory stack index out of range"
+                ));
+            }
+        },
+        [argument] => {
+            return CommandOutput::failure(format!(
+                "popd: {argument}: invalid argument\n{POPD_USAGE}"
+            ));
+        }
+        _ => return CommandOutput::failure(String::from("popd: too many arguments")),
+    };
+
+    entries.remove(position);
+    if position > 0 {
+        return CommandOutput {
+            std_output: Some(listing(&entries, false)),
+            dir_stack: Some(entries.split_off(1)),
+            ..Default::default()
+        };
+    }
+
+    change_to_top("popd", entries)
+}
+
+/// Changes to the first entry of `entries` and keeps the rest as the stack.
+fn change_to_top(name: &str, mut entries: Vec<PathBuf>) -> CommandOutput {
+    if let Err(error) = os::check_directory(&entries[0]) {
+        return CommandOutput::failure(format!(
+            "{name}: {}: {}",
+            entries[0].display(),
+            os::describe_error(&error)
+        ));
+    }
+
+    let output = listing(&entries, false);
+    let stack = entries.split_off(1);
+    CommandOutput {
+        updated_dir: entries.pop(),
+        dir_stack: Some(stack),
+        std_output: Some(output),
+        ..Default::default()
+    }
+}
+
+fn full_stack(current_dir: &Path, stack: &[PathBuf]) -> Vec<PathBuf> {
+    let mut entries = vec![current_dir.to_path_buf()];
+    entries.extend(stack.iter().cloned());
+    entries
+}
+
+/// `+N` counts from the left of the `dirs` listing, starting at zero; `-N` from the right.
+fn stack_index(spec: &str) -> Option<(bool, usize)> {
+    let (from_left, digits) = match spec.split_at_checked(1)? {
+        ("+", digits) => (true, digits),
+        ("-", digits) => (false, digits),
+        _ => return None,
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((from_left, digits.parse().ok()?))
+}
+
+fn resolve_index(spec: &str, len: usize) -> Option<usize> {
+    let (from_left, offset) = stack_index(spec)?;
+    if offset >= len {
+        return None;
+    }
+    Some(if from_left { offset } else { len - 1 - offset })
+}
+
+fn listing(entries: &[PathBuf], long: bool) -> String {
+    let dirs: Vec<String> = entries.iter().map(|dir| show(dir, long)).collect();
+    format!("{}\n", dirs.join(" "))
+}
+
+/// Entries are shown with `$HOME` as `~` unless `-l` asks for full paths.
+fn show(dir: &Path, long: bool) -> String {
+    if long {
+        dir.display().to_string()
+    } else {
+        prompt::abbreviate_home(dir)
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{commands::save_history, shell::{CommandInput, CommandOutput}};
+use crate::shell::{CommandInput, CommandOutput};
 
 pub fn exit(input: CommandInput) -> CommandOutput {
-    save_history(input.command_history);
+    input.command_history.save();
     std::process::exit(0);
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+use crate::{
+    parser,
+    shell::{CommandInput, CommandOutput},
+};
+
+const USAGE: &str = "hash: usage: hash [-r] [-p pathname] [-d] [name ...]";
+
+/// `hash`: lists the remembered command locations with their hit counts. `hash name...` looks
+/// names up in `PATH` and remembers them, `-p path name...` remembers them at `path`, `-d`
+/// forgets them and `-r` forgets everything.
+pub fn hash(input: CommandInput) -> CommandOutput {
+    let mut reset = false;
+    let mut delete = false;
+    let mut pathname: Option<&str> = None;
+    let mut names: Vec<&str> = Vec::new();
+
+    let mut arguments = input.command_arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "--" && names.is_empty() {
+            names.extend(arguments.by_ref().map(String::as_str));
+            break;
+        }
+        // options stop at the first name
+        let Some(flags) = argument
+            .strip_prefix('-')
+            .filter(|flags| !flags.is_empty() && names.is_empty())
+        else {
+            names.push(argument);
+            continue;
+        };
+
+        for (index, flag) in flags.char_indices() {
+            match flag {
+                'r' => reset = true,
+                'd' => delete = true,
+                // the path may follow the flag directly, as in `-p/bin/ls`
+                'p' => {
+                    let rest = &flags[index + 1..];
+                    if !rest.is_empty() {
+                        pathname = Some(rest);
+                        break;
+                    }
+                    let Some(value) = arguments.next() else {
+                        return CommandOutput::failure(String::from(
+                            "hash: -p: option requires an argument",
+                        ));
+                    };
+                    pathname = Some(value);
+                }
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "hash: -{flag}: invalid option\n{USAGE}"
+                    ));
+                }
+            }
+        }
+    }
+
+    if !reset && !delete && pathname.is_none() && names.is_empty() {
+        return CommandOutput::success(list(&input));
+    }
+
+    let mut command_hash = input.command_hash.clone();
+    let mut errors = Vec::new();
+
+    if reset {
+        command_hash.clear();
+    }
+
+    if let Some(pathname) = pathname {
+        if names.is_empty() {
+            return CommandOutput::failure(USAGE.to_string());
+        }
+        let path = match parser::parse_path(pathname, input.current_dir) {
+            Ok(path) => path,
+            Err(message) => return CommandOutput::failure(message),
+        };
+        for name in &names {
+            command_hash.insert(name, &path);
+        }
+    } else if delete {
+        for name in &names {
+            if !command_hash.remove(name) {
+                errors.push(format!("hash: {name}: not found"));
+            }
+        }
+    } else {
+        for name in &names {
+            // like bash, builtins are skipped, and so are paths
+            if input.shell_commands.contains(*name) || name.contains('/') {
+                continue;
+            }
+            match input.os.find_in_path(name) {
+                Some(path) => command_hash.insert(name, &path),
+                None => errors.push(format!("hash: {name}: not found")),
+            }
+        }
+    }
+
+    CommandOutput {
+        std_error: (!errors.is_empty()).then(|| errors.join("\n")),
+        command_hash: Some(command_hash),
+        ..Default::default()
+    }
+}
+
+fn list(input: &CommandInput) -> String {
+    let entries = input.command_hash.entries();
+    if entries.is_empty() {
+        return String::from("hash: hash table empty");
+    }
+
+    let mut output = String::from("hits\tcommand\n");
+    for entry in entries {
+        output += format!("{:>4}\t{}\n", entry.hits, entry.path.display()).as_str();
+    }
+    output
+}
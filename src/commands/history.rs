@@ -1,126 +1,184 @@
-use std::error::Error;
-use std::io::Write;
-use std::{
-    fs::{self, OpenOptions},
-    num::ParseIntError,
-    sync::atomic::{AtomicUsize, Ordering},
+use std::{env, path::PathBuf};
+
+use crate::{
+    os,
+    shell::{CommandInput, CommandOutput, history::History, history_expansion},
 };
 
-use crate::shell::{CommandInput, CommandOutput};
+const USAGE: &str = "history: usage: history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]";
 
-static LAST_APPENDED_INDEX: AtomicUsize = AtomicUsize::new(0);
+pub fn history(input: CommandInput) -> CommandOutput {
+    let mut clear = false;
+    let mut delete: Option<&str> = None;
+    let mut file_action: Option<char> = None;
+    let mut print_expansion = false;
+    let mut store = false;
+    let mut operands: Vec<&str> = Vec::new();
+
+    let mut arguments = input.command_arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "--" && operands.is_empty() {
+            operands.extend(arguments.by_ref().map(String::as_str));
+            break;
+        }
+        // options stop at the first operand
+        let Some(flags) = argument
+            .strip_prefix('-')
+            .filter(|flags| !flags.is_empty() && operands.is_empty())
+        else {
+            operands.push(argument);
+            continue;
+        };
 
-pub fn load_history() -> Vec<String> {
-    if let Some(path) = std::env::var_os("HISTFILE") {
-        match read_path_file(path.to_str().unwrap_or_default()) {
-            Ok(result) => return result,
-            Err(_) => Vec::new()
+        for (index, flag) in flags.char_indices() {
+            match flag {
+                'c' => clear = true,
+                'p' => print_expansion = true,
+                's' => store = true,
+                'a' | 'n' | 'r' | 'w' => {
+                    if file_action.is_some_and(|action| action != flag) {
+                        return CommandOutput::failure(String::from(
+                            "history: cannot use more than one of -anrw",
+                        ));
+                    }
+                    file_action = Some(flag);
+                }
+                // the offset may follow the flag directly, as in `-d5`
+                'd' => {
+                    let rest = &flags[index + 1..];
+                    if !rest.is_empty() {
+                        delete = Some(rest);
+                        break;
+                    }
+                    let Some(value) = arguments.next() else {
+                        return CommandOutput::failure(String::from(
+                            "history: -d: option requires an argument",
+                        ));
+                    };
+                    delete = Some(value);
+                }
+                _ => {
+                    return CommandOutput::failure(format!("history: -{flag}: invalid option\n{USAGE}"));
+                }
+            }
         }
-    } else {
-        Vec::new()
     }
-}
 
-pub fn save_history(content: &[String]) {
-    if let Some(path) = std::env::var_os("HISTFILE") {
-        _ = write_lines_to_file(path.to_str().unwrap_or_default(), content);
+    if print_expansion || store {
+        return expand_or_store(input.command_history, &operands, print_expansion, store);
     }
-}
 
-pub fn history(input: CommandInput) -> CommandOutput {
-    if let Some(arg) = input.command_arguments.first()
-        && matches!(arg.as_str(), "-r" | "-w" | "-a")
-    {
-        let path = if let Some(value) = input.command_arguments.get(1) {
-            value
-        } else {
-            return CommandOutput::failure("missing path".to_string());
+    let mut history = input.command_history.clone();
+
+    if clear {
+        history.clear();
+    }
+
+    if let Some(spec) = delete {
+        let Some((first, last)) = delete_range(&history, spec) else {
+            return CommandOutput::failure(format!("history: {spec}: history position out of range"));
         };
+        history.delete(first, last);
+    }
 
-        let result = if arg == "-r" {
-            read_file_to_output(path)
-        } else if arg == "-w" {
-            write_lines_to_file(path, input.command_history)
-        } else if arg == "-a" {
-            append_content(path, input.command_history)
-        } else {
-            Ok(CommandOutput::empty()) // need to return Err instead of ok. Unexpected situation.
+    if let Some(action) = file_action {
+        let path = match operands.first() {
+            Some(path) => PathBuf::from(path),
+            None => match env::var_os("HISTFILE").filter(|path| !path.is_empty()) {
+                Some(path) => PathBuf::from(path),
+                None => return CommandOutput::failure(String::from("history: HISTFILE not set")),
+            },
         };
 
-        match result {
-            Ok(output) => return output,
-            Err(error) => return CommandOutput::failure(error.to_string()),
+        let result = match action {
+            'a' => history.append(&path),
+            'n' => history.read_new(&path),
+            'r' => history.read(&path),
+            _ => history.write(&path),
+        };
+        if let Err(error) = result {
+            return CommandOutput::failure(format!("history: {}: {error}", path.display()));
         }
     }
 
-    let size: Option<Result<usize, ParseIntError>> =
-        input.command_arguments.first().map(|s| s.as_str().parse());
-
-    let (initial_value, enumeration) = match size {
-        None => (1, input.command_history.iter().enumerate()),
-        Some(value) => {
-            let value = match value {
-                Err(error) => {
-                    return CommandOutput::failure(format!(
-                        "Failed to parse history argument: {}",
-                        error
-                    ));
-                }
-                Ok(v) => v,
-            };
-
-            let start_position: usize = input.command_history.len().saturating_sub(value);
-            (
-                start_position + 1,
-                input.command_history[start_position..].iter().enumerate(),
-            )
-        }
-    };
-
-    let mut output = String::new();
-    for (position, command) in enumeration {
-        output += format!("{} {}\n", (position + initial_value), command).as_str();
+    if clear || delete.is_some() || file_action.is_some() {
+        return CommandOutput::history_update(history);
     }
-    CommandOutput::success(output)
-}
 
-fn read_file_to_output(path: &str) -> Result<CommandOutput, Box<dyn Error>> {
-    let paths = read_path_file(path)?;
-    Ok(CommandOutput::history_update(paths))
-}
+    let size = match operands.as_slice() {
+        [] => None,
+        [count] => match count.parse::<usize>() {
+            Ok(size) => Some(size),
+            Err(_) => return CommandOutput::failure(format!("history: {count}: numeric argument required")),
+        },
+        _ => return CommandOutput::failure(String::from("history: too many arguments")),
+    };
 
-fn read_path_file(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let result = fs::read_to_string(path)?;
+    CommandOutput::success(list(&history, size))
+}
 
-    let mut paths: Vec<String> = Vec::new();
+/// `-p` prints each argument after history expansion, `-s` stores them as a single entry.
+fn expand_or_store(history: &History, operands: &[&str], print_expansion: bool, store: bool) -> CommandOutput {
+    let mut output = String::new();
 
-    for line in result.lines() {
-        if !line.is_empty() {
-            paths.push(line.to_string());
+    if print_expansion {
+        for operand in operands {
+            match history_expansion::expand(operand, history.entries(), history.first_number()) {
+                Ok(expansion) => output += format!("{}\n", expansion.line).as_str(),
+                Err(message) => return CommandOutput::failure(format!("history: {message}")),
+            }
         }
     }
 
-    Ok(paths)
+    let mut result = CommandOutput::success(output);
+    if store && !operands.is_empty() {
+        let mut history = history.clone();
+        history.store(&operands.join(" "));
+        result.command_history = Some(history);
+    }
+    result
 }
 
-fn write_lines_to_file(path: &str, content: &[String]) -> Result<CommandOutput, Box<dyn Error>> {
-    fs::write(path, format!("{}\n", content.join("\n")))?;
-    Ok(CommandOutput::empty())
-}
+/// History numbers for `-d offset` or `-d start-end`; negative offsets count back from the end,
+/// so `-1` is the `history -d` line itself.
+fn delete_range(history: &History, spec: &str) -> Option<(usize, usize)> {
+    let position = |text: &str| -> Option<usize> {
+        let offset: i64 = text.parse().ok()?;
+        if offset < 0 {
+            usize::try_from(history.next_number() as i64 + offset).ok()
+        } else {
+            usize::try_from(offset).ok()
+        }
+    };
 
-fn append_content(path: &str, content: &[String]) -> Result<CommandOutput, Box<dyn Error>> {
-    if LAST_APPENDED_INDEX.load(Ordering::Relaxed) >= content.len() {
-        return Ok(CommandOutput::empty());
-    }
+    // the range separator is a `-` that doesn't start a (negative) number
+    let separator = spec.char_indices().skip(1).find(|&(_, c)| c == '-').map(|(index, _)| index);
+    let (first, last) = match separator {
+        Some(index) => (position(&spec[..index])?, position(&spec[index + 1..])?),
+        None => (position(spec)?, position(spec)?),
+    };
 
-    let append_content = &content[LAST_APPENDED_INDEX.load(Ordering::Relaxed)..];
-    LAST_APPENDED_INDEX.store(content.len(), Ordering::Relaxed);
+    let in_history = |number: usize| number >= history.first_number() && number < history.next_number();
+    (in_history(first) && in_history(last) && first <= last).then_some((first, last))
+}
+
+fn list(history: &History, size: Option<usize>) -> String {
+    let entries = history.entries();
+    let start_position = size.map_or(0, |value| entries.len().saturating_sub(value));
+    let initial_value = start_position + history.first_number();
+    let time_format = env::var("HISTTIMEFORMAT").ok();
 
-    if !fs::exists(path)? {
-        write_lines_to_file(path, append_content)
-    } else {
-        let file = OpenOptions::new().create(true).append(true).open(path)?;
-        writeln!(&file, "{}", append_content.join("\n"))?;
-        Ok(CommandOutput::empty())
+    let mut output = String::new();
+    for (position, entry) in entries[start_position..].iter().enumerate() {
+        output += format!("{} ", position + initial_value).as_str();
+        // like bash, lines read without a timestamp show `??`
+        if let Some(format) = &time_format {
+            match entry.timestamp {
+                Some(timestamp) => output += os::format_time(timestamp, format).as_str(),
+                None => output += "?? ",
+            }
+        }
+        output += format!("{}\n", entry.line).as_str();
     }
+    output
 }
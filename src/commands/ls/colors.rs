@@ -0,0 +1,136 @@
+use std::{
+    env,
+    fs::Metadata,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::Path,
+};
+
+use crate::parser;
+
+/// GNU ls' built-in colors, used when `LS_COLORS` isn't set.
+const DEFAULT_LS_COLORS: &str = "rs=0:di=01;34:ln=01;36:mh=00:pi=40;33:so=01;35:do=01;35:bd=40;33;01:\
+cd=40;33;01:or=40;31;01:mi=00:su=37;41:sg=30;43:ca=00:tw=30;42:ow=34;42:st=37;44:ex=01;32";
+
+/// Colors parsed from `LS_COLORS`: two-letter file type keys (`di`, `ln`, `ex`, ...) and `*.ext`
+/// patterns for regular files.
+pub struct LsColors {
+    types: Vec<(String, String)>,
+    patterns: Vec<(String, String)>,
+}
+
+impl LsColors {
+    pub fn from_env() -> LsColors {
+        let value = env::var("LS_COLORS")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| DEFAULT_LS_COLORS.to_string());
+        LsColors::parse(&value)
+    }
+
+    pub fn parse(value: &str) -> LsColors {
+        let mut colors = LsColors {
+            types: Vec::new(),
+            patterns: Vec::new(),
+        };
+
+        for rule in value.split(':') {
+            let Some((key, code)) = rule.split_once('=') else {
+                continue;
+            };
+            if key.starts_with('*') {
+                colors.patterns.push((key.to_string(), code.to_string()));
+            } else {
+                colors.types.push((key.to_string(), code.to_string()));
+            }
+        }
+
+        colors
+    }
+
+    /// Paints `name` with the color of the entry at `path`, whose own (not followed) metadata is given.
+    pub fn paint(&self, name: &str, path: &Path, metadata: &Metadata) -> String {
+        match self.code_for(path, metadata) {
+            Some(code) => format!("\x1b[{code}m{name}\x1b[0m"),
+            None => name.to_string(),
+        }
+    }
+
+    fn code_for(&self, path: &Path, metadata: &Metadata) -> Option<&str> {
+        let file_type = metadata.file_type();
+        let mode = metadata.permissions().mode();
+
+        let key = if file_type.is_symlink() {
+            match path.metadata() {
+                // `ln=target` colors the link like what it points to
+                Ok(target) if self.get("ln") == Some("target") => {
+                    return self.code_for(path, &target);
+                }
+                Ok(_) => "ln",
+                Err(_) => "or",
+            }
+        } else if file_type.is_dir() {
+            match (mode & 0o1000 != 0, mode & 0o002 != 0) {
+                (true, true) => "tw",
+                (false, true) => "ow",
+                (true, false) => "st",
+                (false, false) => "di",
+            }
+        } else if file_type.is_fifo() {
+            "pi"
+        } else if file_type.is_socket() {
+            "so"
+        } else if file_type.is_block_device() {
+            "bd"
+        } else if file_type.is_char_device() {
+            "cd"
+        } else if mode & 0o4000 != 0 {
+            "su"
+        } else if mode & 0o2000 != 0 {
+            "sg"
+        } else if mode & 0o111 != 0 {
+            "ex"
+        } else {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            let pattern = self
+                .patterns
+                .iter()
+                .rev()
+                .find(|(pattern, _)| matches(pattern, &name));
+            return match pattern {
+                Some((_, code)) => Some(code),
+                None => self.get("fi"),
+            };
+        };
+
+        // special files without their own color fall back to the plain file type
+        self.get(key).or_else(|| match key {
+            "tw" | "ow" | "st" => self.get("di"),
+            "su" | "sg" => self.get("ex"),
+            _ => None,
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.types
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, code)| code.as_str())
+            .filter(|code| !code.is_empty() && *code != "0" && *code != "00")
+    }
+}
+
+/// `*.ext` patterns match the end of the name, ignoring case like GNU ls; others are globs.
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_prefix('*') {
+        Some(suffix) if !suffix.contains(['*', '?', '[']) => {
+            name.len() >= suffix.len()
+                && name.is_char_boundary(name.len() - suffix.len())
+                && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+        }
+        _ => parser::glob_match(pattern, name),
+    }
+}
@@ -0,0 +1,638 @@
+mod colors;
+mod tree;
+
+use crate::{
+    git::{DirectoryStatus, StatusCache},
+    os, parser,
+    shell::{CommandInput, CommandOutput},
+};
+use colors::LsColors;
+use is_executable::IsExecutable;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    env,
+    fs::{self, Metadata},
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Entries modified longer ago than this (about six months) show their year instead of the time.
+const RECENT_SECONDS: i64 = 15_778_476;
+const USAGE: &str = "Usage: dir [-1aAdhlrRStX] [--color[=WHEN]] [--tree [-L depth] [--gitignore] [--icons]] [--no-git] [path ...]";
+/// Spaces between columns in the multi-column layout.
+const COLUMN_GAP: usize = 2;
+
+#[derive(Default)]
+struct Options {
+    all: bool,
+    almost_all: bool,
+    long: bool,
+    human: bool,
+    recursive: bool,
+    directory: bool,
+    sort: Sort,
+    reverse: bool,
+    one_per_line: bool,
+    /// Set when names are colored.
+    colors: Option<LsColors>,
+    /// Terminal width for the multi-column layout, unset when printing one entry per line.
+    width: Option<usize>,
+    tree: bool,
+    /// Directory levels shown by `--tree`.
+    depth: Option<usize>,
+    /// Whether `--tree` leaves out what `.gitignore` and `.ignore` files exclude.
+    gitignore: bool,
+    icons: bool,
+    /// Whether directory listings show git status markers (on unless `--no-git`).
+    git: bool,
+    /// User and group names by id for the `-l` owner columns, read once per listing.
+    user_names: HashMap<u32, String>,
+    group_names: HashMap<u32, String>,
+}
+
+#[derive(Default, Clone, Copy)]
+enum Sort {
+    #[default]
+    Name,
+    Time,
+    Size,
+    Extension,
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    /// Metadata of the entry itself, not of what a symlink points to.
+    metadata: Metadata,
+}
+
+impl Entry {
+    fn new(name: String, path: PathBuf) -> io::Result<Entry> {
+        let metadata = fs::symlink_metadata(&path)?;
+        Ok(Entry {
+            name,
+            path,
+            metadata,
+        })
+    }
+
+    /// Folders are listed first, then executables, then everything else.
+    fn kind(&self) -> u8 {
+        if self.path.is_dir() {
+            0
+        } else if self.path.is_executable() {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn display_name(&self, options: &Options) -> String {
+        match &options.colors {
+            Some(colors) => colors.paint(&self.name, &self.path, &self.metadata),
+            None => self.name.clone(),
+        }
+    }
+
+    /// `-> target` for symlinks in the long format, colored like the target.
+    fn link_target(&self, options: &Options) -> Option<String> {
+        let target = fs::read_link(&self.path).ok()?;
+        let target_name = target.display().to_string();
+        let target_path = self.path.parent().unwrap_or(Path::new("/")).join(&target);
+
+        let painted = match (&options.colors, fs::symlink_metadata(&target_path)) {
+            (Some(colors), Ok(metadata)) => colors.paint(&target_name, &target_path, &metadata),
+            _ => target_name,
+        };
+        Some(format!(" -> {painted}"))
+    }
+}
+
+pub fn ls(input: CommandInput) -> CommandOutput {
+    let mut options = Options {
+        git: true,
+        ..Default::default()
+    };
+    let mut operands: Vec<&str> = Vec::new();
+    let mut color = "auto";
+
+    let mut arguments = input.command_arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "--" {
+            operands.extend(arguments.by_ref().map(String::as_str));
+            break;
+        }
+        if let Some(option) = argument.strip_prefix("--") {
+            match option.split_once('=') {
+                None if option == "color" => color = "always",
+                None if option == "tree" => options.tree = true,
+                None if option == "gitignore" => options.gitignore = true,
+                None if option == "icons" => options.icons = true,
+                None if option == "no-git" => options.git = false,
+                Some(("color", when)) => color = when,
+                Some(("level", depth)) => match parse_depth(depth) {
+                    Ok(depth) => options.depth = Some(depth),
+                    Err(message) => return CommandOutput::failure(message),
+                },
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "dir: unrecognized option '{argument}'\n{USAGE}"
+                    ));
+                }
+            }
+            continue;
+        }
+        let Some(flags) = argument.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            operands.push(argument);
+            continue;
+        };
+
+        for (index, flag) in flags.char_indices() {
+            match flag {
+                // the depth may follow the flag directly, as in `-L2`
+                'L' => {
+                    let rest = &flags[index + 1..];
+                    let Some(depth) = Some(rest)
+                        .filter(|rest| !rest.is_empty())
+                        .or_else(|| arguments.next().map(String::as_str))
+                    else {
+                        return CommandOutput::failure(format!(
+                            "dir: option requires an argument -- 'L'\n{USAGE}"
+                        ));
+                    };
+                    match parse_depth(depth) {
+                        Ok(depth) => options.depth = Some(depth),
+                        Err(message) => return CommandOutput::failure(message),
+                    }
+                    break;
+                }
+                'a' => options.all = true,
+                'A' => options.almost_all = true,
+                'l' => options.long = true,
+                'h' => options.human = true,
+                'R' => options.recursive = true,
+                'd' => options.directory = true,
+                't' => options.sort = Sort::Time,
+                'S' => options.sort = Sort::Size,
+                'X' => options.sort = Sort::Extension,
+                'r' => options.reverse = true,
+                '1' => options.one_per_line = true,
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "dir: invalid option -- '{flag}'\n{USAGE}"
+                    ));
+                }
+            }
+        }
+    }
+
+    if options.depth.is_some() && !options.tree {
+        return CommandOutput::failure(format!(
+            "dir: the level (-L, --level) can only be used with --tree\n{USAGE}"
+        ));
+    }
+
+    let use_colors = match color {
+        "always" | "yes" | "force" => true,
+        "never" | "no" | "none" => false,
+        "auto" | "tty" | "if-tty" => input.terminal_output,
+        _ => {
+            return CommandOutput::failure(format!(
+                "dir: invalid argument '{color}' for '--color'\nValid arguments are: 'always', 'never', 'auto'"
+            ));
+        }
+    };
+    if use_colors {
+        options.colors = Some(LsColors::from_env());
+    }
+    if input.terminal_output && !options.one_per_line {
+        options.width = Some(terminal_width());
+    }
+    if options.long {
+        options.user_names = os::user_names();
+        options.group_names = os::group_names();
+    }
+
+    let show_headers = operands.len() > 1 || options.recursive;
+    if operands.is_empty() {
+        operands.push(".");
+    }
+
+    let mut errors: Vec<String> = Vec::new();
+    let mut files: Vec<Entry> = Vec::new();
+    let mut directories: Vec<Entry> = Vec::new();
+
+    for operand in operands {
+        let entry = parser::parse_path(operand, input.current_dir)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))
+            .and_then(|path| Entry::new(operand.to_string(), path));
+
+        match entry {
+            Ok(entry) if entry.path.is_dir() && !options.directory => directories.push(entry),
+            Ok(entry) => files.push(entry),
+            Err(error) => errors.push(format!(
+                "dir: cannot access '{operand}': {}",
+                os::describe_error(&error)
+            )),
+        }
+    }
+
+    let mut blocks: Vec<String> = Vec::new();
+    if options.tree {
+        let mut summary = tree::Summary::default();
+        for entry in files.iter().chain(&directories) {
+            blocks.push(tree::render(entry, &options, &mut summary));
+        }
+        blocks.push(summary.describe());
+        return output(blocks, errors);
+    }
+
+    if !files.is_empty() {
+        sort(&mut files, &options);
+        blocks.push(render(&files, &options, false, None));
+    }
+
+    sort(&mut directories, &options);
+    let mut git_statuses = StatusCache::default();
+    for directory in directories {
+        list_directory(
+            &directory.name,
+            &directory.path,
+            &options,
+            show_headers,
+            &mut git_statuses,
+            &mut blocks,
+            &mut errors,
+        );
+    }
+
+    output(blocks, errors)
+}
+
+fn output(blocks: Vec<String>, errors: Vec<String>) -> CommandOutput {
+    let mut output = CommandOutput::empty();
+    if !blocks.is_empty() {
+        output.std_output = Some(blocks.join("\n\n"));
+    }
+    if !errors.is_empty() {
+        output.std_error = Some(errors.join("\n"));
+    }
+    output
+}
+
+fn parse_depth(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(depth) if depth > 0 => Ok(depth),
+        _ => Err(format!(
+            "dir: invalid level, must be greater than 0: '{value}'"
+        )),
+    }
+}
+
+/// Lists a directory as one block, then its subdirectories with `-R`.
+fn list_directory(
+    label: &str,
+    path: &Path,
+    options: &Options,
+    show_header: bool,
+    git_statuses: &mut StatusCache,
+    blocks: &mut Vec<String>,
+    errors: &mut Vec<String>,
+) {
+    let entries = match read_entries(path, options) {
+        Ok(entries) => entries,
+        Err(error) => {
+            errors.push(format!(
+                "dir: cannot open directory '{label}': {}",
+                os::describe_error(&error)
+            ));
+            return;
+        }
+    };
+
+    let git_status = if options.git {
+        git_statuses.directory_status(path)
+    } else {
+        None
+    };
+    let listing = render(&entries, options, true, git_status.as_ref());
+    blocks.push(match (show_header, listing.is_empty()) {
+        (true, true) => format!("{label}:"),
+        (true, false) => format!("{label}:\n{listing}"),
+        (false, _) => listing,
+    });
+
+    if options.recursive {
+        for entry in &entries {
+            if entry.metadata.is_dir() && entry.name != "." && entry.name != ".." {
+                let label = format!("{}/{}", label.trim_end_matches('/'), entry.name);
+                list_directory(&label, &entry.path, options, true, git_statuses, blocks, errors);
+            }
+        }
+    }
+}
+
+fn read_entries(path: &Path, options: &Options) -> io::Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    if options.all {
+        entries.push(Entry::new(String::from("."), path.to_path_buf())?);
+        entries.push(Entry::new(String::from(".."), path.join(".."))?);
+    }
+
+    for dir_entry in fs::read_dir(path)? {
+        let Ok(dir_entry) = dir_entry else {
+            continue;
+        };
+
+        let name = dir_entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') && !options.all && !options.almost_all {
+            continue;
+        }
+        if let Ok(entry) = Entry::new(name, dir_entry.path()) {
+            entries.push(entry);
+        }
+    }
+
+    sort(&mut entries, options);
+    Ok(entries)
+}
+
+/// Sorts by name, time, size or extension, with ties by name. Only the default order lists
+/// folders first, then executables, then everything else; `-t`, `-S`, `-X` and `-r` sort the
+/// entries as a whole, as ls does.
+fn sort(entries: &mut [Entry], options: &Options) {
+    let grouped = matches!(options.sort, Sort::Name) && !options.reverse;
+
+    entries.sort_by_cached_key(|entry| {
+        let kind = if grouped { entry.kind() } else { 0 };
+        // newest and largest first, as in ls
+        let (time, size) = match options.sort {
+            Sort::Time => (entry.metadata.mtime(), 0),
+            Sort::Size => (0, entry.metadata.len()),
+            Sort::Name | Sort::Extension => (0, 0),
+        };
+        let extension = match options.sort {
+            Sort::Extension => extension(&entry.name).to_string(),
+            _ => String::new(),
+        };
+        (kind, Reverse(time), Reverse(size), extension, entry.name.clone())
+    });
+
+    if options.reverse {
+        entries.reverse();
+    }
+}
+
+fn extension(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => &name[dot + 1..],
+        _ => "",
+    }
+}
+
+/// One line per entry, or the `-l` table with a `total` line. Directory listings come with the
+/// git status of the directory, when it's in a work tree.
+fn render(
+    entries: &[Entry],
+    options: &Options,
+    is_directory: bool,
+    git_status: Option<&DirectoryStatus>,
+) -> String {
+    let markers: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            git_status
+                .map(|status| status.markers(&entry.name))
+                .unwrap_or_default()
+        })
+        .collect();
+    let marker_width = markers.iter().map(String::len).max().unwrap_or(0);
+    let marker_column = |markers: &str| {
+        if marker_width == 0 {
+            String::new()
+        } else {
+            let padding = " ".repeat(marker_width - markers.len() + 1);
+            format!("{}{padding}", paint_markers(markers, options))
+        }
+    };
+
+    if !options.long {
+        let names: Vec<String> = entries
+            .iter()
+            .zip(&markers)
+            .map(|(entry, markers)| {
+                format!("{}{}", marker_column(markers), entry.display_name(options))
+            })
+            .collect();
+        let widths: Vec<usize> = entries
+            .iter()
+            .map(|entry| {
+                entry.name.chars().count()
+                    + if marker_width > 0 {
+                        marker_width + 1
+                    } else {
+                        0
+                    }
+            })
+            .collect();
+        return match options.width {
+            Some(width) => columns(&names, &widths, width),
+            None => names.join("\n"),
+        };
+    }
+
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            let metadata = &entry.metadata;
+            let uid = metadata.uid();
+            let gid = metadata.gid();
+            [
+                metadata.nlink().to_string(),
+                options.user_names.get(&uid).cloned().unwrap_or_else(|| uid.to_string()),
+                options.group_names.get(&gid).cloned().unwrap_or_else(|| gid.to_string()),
+                format_size(metadata.len(), options.human),
+                format_mtime(metadata.mtime()),
+            ]
+        })
+        .collect();
+
+    let width = |column: usize| rows.iter().map(|row| row[column].len()).max().unwrap_or(0);
+    let widths = [width(0), width(1), width(2), width(3)];
+
+    let mut lines: Vec<String> = Vec::new();
+    if is_directory {
+        // `blocks` counts 512-byte units, ls reports 1K blocks
+        let blocks: u64 = entries.iter().map(|entry| entry.metadata.blocks()).sum();
+        lines.push(format!(
+            "total {}",
+            format_size(blocks * 512 / 1024, options.human)
+        ));
+    }
+
+    for ((entry, row), markers) in entries.iter().zip(rows).zip(&markers) {
+        lines.push(format!(
+            "{} {:>w0$} {:<w1$} {:<w2$} {:>w3$} {} {}{}{}",
+            format_mode(&entry.metadata),
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            marker_column(markers),
+            entry.display_name(options),
+            entry.link_target(options).unwrap_or_default(),
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Names laid out down then across in as many columns as fit in `width`, like GNU ls.
+fn columns(names: &[String], widths: &[usize], width: usize) -> String {
+    let mut layout = (
+        names.len().max(1),
+        vec![widths.iter().copied().max().unwrap_or(0)],
+    );
+    for column_count in (2..=names.len()).rev() {
+        let rows = names.len().div_ceil(column_count);
+        let column_widths: Vec<usize> = widths
+            .chunks(rows)
+            .map(|column| column.iter().copied().max().unwrap_or(0))
+            .collect();
+        let total: usize =
+            column_widths.iter().sum::<usize>() + COLUMN_GAP * (column_widths.len() - 1);
+        if total <= width {
+            layout = (rows, column_widths);
+            break;
+        }
+    }
+
+    let (rows, column_widths) = layout;
+    let mut lines: Vec<String> = Vec::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let index = column * rows + row;
+            let Some(name) = names.get(index) else {
+                break;
+            };
+            line.push_str(name);
+            if names.get(index + rows).is_some() {
+                line.push_str(&" ".repeat(column_width - widths[index] + COLUMN_GAP));
+            }
+        }
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// Git markers in the colors `git status` uses: staged green, conflicts and untracked red.
+fn paint_markers(markers: &str, options: &Options) -> String {
+    if options.colors.is_none() {
+        return markers.to_string();
+    }
+
+    markers
+        .chars()
+        .map(|marker| {
+            let code = match marker {
+                '*' => "33",
+                '+' => "32",
+                '%' => "31",
+                '!' => "01;31",
+                _ => "90",
+            };
+            format!("\x1b[{code}m{marker}\x1b[0m")
+        })
+        .collect()
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .or_else(|| {
+            crossterm::terminal::size()
+                .ok()
+                .map(|(columns, _)| usize::from(columns))
+        })
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+/// File type and permissions as `ls -l` shows them, e.g. `drwxr-xr-x`.
+fn format_mode(metadata: &Metadata) -> String {
+    let file_type = metadata.file_type();
+    let type_char = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    };
+
+    let mode = metadata.permissions().mode();
+    let mut text = String::from(type_char);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+
+    text
+}
+
+/// Size in bytes, or with `-h` in powers of 1024 rounded up like ls (`4.0K`, `23M`).
+fn format_size(size: u64, human: bool) -> String {
+    if !human || size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64;
+    let mut unit = 'B';
+    for next_unit in ['K', 'M', 'G', 'T', 'P', 'E'] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+
+    let tenths = (value * 10.0).ceil() / 10.0;
+    if tenths < 10.0 {
+        format!("{tenths:.1}{unit}")
+    } else {
+        format!("{:.0}{unit}", value.ceil())
+    }
+}
+
+fn format_mtime(mtime: i64) -> String {
+    if (os::now() - mtime).abs() < RECENT_SECONDS {
+        os::format_time(mtime, "%b %e %H:%M")
+    } else {
+        os::format_time(mtime, "%b %e  %Y")
+    }
+}
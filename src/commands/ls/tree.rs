@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use crate::git::{IgnoreRules, Repository};
+
+use super::{Entry, Options, format_size, read_entries};
+
+/// Icons for `--icons`, by the folder/executable/other classification of `Entry::kind`.
+const ICONS: [&str; 3] = ["📁", "⚙️", "📄"];
+
+/// Totals shown under the trees.
+#[derive(Default)]
+pub struct Summary {
+    directories: usize,
+    files: usize,
+    bytes: u64,
+}
+
+impl Summary {
+    pub fn describe(&self) -> String {
+        let directories = if self.directories == 1 {
+            "directory"
+        } else {
+            "directories"
+        };
+        let files = if self.files == 1 { "file" } else { "files" };
+        let size = if self.bytes < 1024 {
+            format!("{} bytes", self.bytes)
+        } else {
+            format_size(self.bytes, true)
+        };
+        format!(
+            "{} {directories}, {} {files}, {size}",
+            self.directories, self.files
+        )
+    }
+}
+
+/// `dir --tree`: the directory and everything below it, down to `--level` directories deep.
+pub fn render(root: &Entry, options: &Options, summary: &mut Summary) -> String {
+    let mut walk = TreeWalk {
+        options,
+        rules: IgnoreRules::default(),
+        summary,
+        lines: vec![label(root, options)],
+    };
+
+    let base = if options.gitignore {
+        walk.load_parent_rules(&root.path).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    walk.visit(&root.path, &base, "", 1);
+
+    walk.lines.join("\n")
+}
+
+struct TreeWalk<'a> {
+    options: &'a Options,
+    rules: IgnoreRules,
+    summary: &'a mut Summary,
+    lines: Vec<String>,
+}
+
+impl TreeWalk<'_> {
+    /// Inside a git work tree, loads the ignore rules that apply above `root` and returns the
+    /// path of `root` relative to the work tree, which ignore patterns are matched against.
+    fn load_parent_rules(&mut self, root: &Path) -> Option<String> {
+        let repository = Repository::discover(root)?;
+        let work_tree = repository.work_tree.canonicalize().ok()?;
+        let root = root.canonicalize().ok()?;
+        let relative = root.strip_prefix(&work_tree).ok()?;
+
+        self.rules = IgnoreRules::for_git_dir(&repository.git_dir);
+        let mut base = String::new();
+        let mut dir = repository.work_tree.clone();
+        for component in relative.components() {
+            self.add_ignore_files(&dir, &base);
+            let name = component.as_os_str().to_string_lossy();
+            base = if base.is_empty() {
+                name.to_string()
+            } else {
+                format!("{base}/{name}")
+            };
+            dir.push(component);
+        }
+
+        Some(base)
+    }
+
+    fn add_ignore_files(&mut self, dir: &Path, base: &str) {
+        self.rules.add_file(&dir.join(".gitignore"), base);
+        self.rules.add_file(&dir.join(".ignore"), base);
+    }
+
+    fn visit(&mut self, path: &Path, base: &str, prefix: &str, depth: usize) {
+        let saved_rules = self.rules.len();
+        if self.options.gitignore {
+            self.add_ignore_files(path, base);
+        }
+
+        let Ok(entries) = read_entries(path, self.options) else {
+            self.rules.truncate(saved_rules);
+            return;
+        };
+        let entries: Vec<(Entry, String)> = entries
+            .into_iter()
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .map(|entry| {
+                let relative = if base.is_empty() {
+                    entry.name.clone()
+                } else {
+                    format!("{base}/{}", entry.name)
+                };
+                (entry, relative)
+            })
+            .filter(|(entry, relative)| {
+                !self.options.gitignore
+                    || entry.name != ".git"
+                        && !self.rules.is_ignored(relative, entry.metadata.is_dir())
+            })
+            .collect();
+
+        for (index, (entry, relative)) in entries.iter().enumerate() {
+            let last = index + 1 == entries.len();
+            let branch = if last { "└── " } else { "├── " };
+            self.lines
+                .push(format!("{prefix}{branch}{}", label(entry, self.options)));
+
+            if entry.metadata.is_dir() {
+                self.summary.directories += 1;
+                if self.options.depth.is_none_or(|max_depth| depth < max_depth) {
+                    let child_prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+                    self.visit(&entry.path, relative, &child_prefix, depth + 1);
+                }
+            } else {
+                self.summary.files += 1;
+                self.summary.bytes += entry.metadata.len();
+            }
+        }
+
+        self.rules.truncate(saved_rules);
+    }
+}
+
+/// Name of a tree line: the size with `-l`, the icon with `--icons`, and symlink targets.
+fn label(entry: &Entry, options: &Options) -> String {
+    let mut label = String::new();
+    if options.long {
+        label.push_str(&format!(
+            "[{:>5}]  ",
+            format_size(entry.metadata.len(), options.human)
+        ));
+    }
+    if options.icons {
+        label.push_str(ICONS[usize::from(entry.kind())]);
+        label.push(' ');
+    }
+    label.push_str(&entry.display_name(options));
+    if entry.metadata.is_symlink()
+        && let Some(target) = entry.link_target(options)
+    {
+        label.push_str(&target);
+    }
+    label
+}
@@ -1,19 +1,30 @@
 mod cd;
+mod command;
+mod complete;
+mod dirs;
 mod echo;
 mod exit;
+mod hash;
 mod ls;
 mod pwd;
+mod resolve;
 mod run;
 mod type_fn;
+mod which;
 mod history;
+mod z;
 
 pub use cd::cd;
+pub use command::command;
+pub use complete::complete;
+pub use dirs::{dirs, popd, pushd};
 pub use echo::echo;
 pub use exit::exit;
+pub use hash::hash;
 pub use ls::ls;
 pub use pwd::pwd;
 pub use run::run_program;
 pub use type_fn::type_fn;
+pub use which::which;
 pub use history::history;
-pub use history::load_history;
-pub use history::save_history;
\ No newline at end of file
+pub use z::{z, zi};
\ No newline at end of file
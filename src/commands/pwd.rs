@@ -1,5 +1,33 @@
-use crate::shell::{CommandInput, CommandOutput};
+use crate::{
+    os,
+    shell::{CommandInput, CommandOutput},
+};
 
+const USAGE: &str = "pwd: usage: pwd [-LP]";
+
+/// `pwd [-L|-P]`: the logical path `cd` followed by default, or with `-P` the physical one, with
+/// every symlink resolved.
 pub fn pwd(input: CommandInput) -> CommandOutput {
-    CommandOutput::success(input.current_dir.display().to_string())
-}
\ No newline at end of file
+    let mut physical = false;
+    for argument in input.command_arguments {
+        let Some(flags) = argument.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            return CommandOutput::failure(String::from("pwd: too many arguments"));
+        };
+        for flag in flags.chars() {
+            match flag {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => return CommandOutput::failure(format!("pwd: -{flag}: invalid option\n{USAGE}")),
+            }
+        }
+    }
+
+    if !physical {
+        return CommandOutput::success(input.current_dir.display().to_string());
+    }
+
+    match input.current_dir.canonicalize() {
+        Ok(dir) => CommandOutput::success(dir.display().to_string()),
+        Err(error) => CommandOutput::failure(format!("pwd: {}", os::describe_error(&error))),
+    }
+}
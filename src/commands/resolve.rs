@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use crate::shell::{CommandInput, CommandOutput};
+
+/// What a command name runs: shared by `type`, `which` and `command`, so they agree with each
+/// other and with how the main loop dispatches commands.
+pub enum Resolution {
+    Builtin,
+    File(PathBuf),
+    /// A file whose location is remembered in the hash table.
+    Hashed(PathBuf),
+}
+
+impl Resolution {
+    /// The one-word kind `type -t` prints.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Resolution::Builtin => "builtin",
+            Resolution::File(_) | Resolution::Hashed(_) => "file",
+        }
+    }
+
+    /// The sentence `type` prints.
+    pub fn describe(&self, name: &str) -> String {
+        match self {
+            Resolution::Builtin => format!("{name} is a shell builtin"),
+            Resolution::File(path) => format!("{name} is {}", path.display()),
+            Resolution::Hashed(path) => format!("{name} is hashed ({})", path.display()),
+        }
+    }
+
+    /// The file it runs, if it isn't a builtin.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Resolution::Builtin => None,
+            Resolution::File(path) | Resolution::Hashed(path) => Some(path),
+        }
+    }
+}
+
+/// Looks `name` up the way the shell runs it: builtins first, then executables. Only the first
+/// match is returned unless `all` is set; `files_only` skips builtins.
+pub fn resolve(name: &str, input: &CommandInput, all: bool, files_only: bool) -> Vec<Resolution> {
+    let mut resolutions = Vec::new();
+
+    if !files_only && input.shell_commands.contains(name) {
+        resolutions.push(Resolution::Builtin);
+        if !all {
+            return resolutions;
+        }
+    }
+
+    if !all && let Some(path) = input.command_hash.get(name) {
+        resolutions.push(Resolution::Hashed(path));
+    } else if all {
+        let executables = input.os.find_all_executables(name, input.current_dir);
+        resolutions.extend(executables.into_iter().map(Resolution::File));
+    } else if let Some(path) = input.os.find_executable(name, input.current_dir) {
+        resolutions.push(Resolution::File(path));
+    }
+
+    resolutions
+}
+
+/// Output of a lookup builtin: what it found, the names it couldn't find (if it reports them),
+/// and a failing status whenever some name wasn't found, even silently.
+pub fn report(output: String, errors: Vec<String>, found_all: bool) -> CommandOutput {
+    CommandOutput {
+        std_output: (!output.is_empty()).then_some(output),
+        std_error: (!errors.is_empty()).then(|| errors.join("\n")),
+        status: Some(if found_all { 0 } else { 1 }),
+        ..Default::default()
+    }
+}
@@ -1,22 +1,30 @@
 use std::{
     io::Write,
+    os::unix::process::CommandExt,
+    path::PathBuf,
     process::{Child, ChildStdout, Command, Stdio},
 };
 
 use crate::shell::{CommandInput};
 
+/// Starts an external command. Along with the child comes the `PATH` location it was started
+/// from, if any, for the hash table.
 pub fn run_program(
     input: CommandInput,
     previous_stdout: &mut Option<ChildStdout>,
     is_last: bool,
     has_redirect: bool,
-) -> Result<Child, String> {
-    if input
-        .os
-        .find_executable(input.command_name, input.current_dir)
-        .is_none()
-    {
-        return Err(format!("{}: not found", input.command_name));
+) -> Result<(Child, Option<PathBuf>), String> {
+    let (executable, hashable) = match input.command_hash.get(input.command_name) {
+        Some(path) => (path.clone(), Some(path)),
+        None => match input.os.find_executable(input.command_name, input.current_dir) {
+            Some(path) => {
+                // executables in the current folder depend on it, so they aren't remembered
+                let in_path = input.os.find_in_path(input.command_name).filter(|found| *found == path);
+                (path, in_path)
+            }
+            None => return Err(format!("{}: not found", input.command_name)),
+        },
     };
 
     let stdin = match previous_stdout.take() {
@@ -29,7 +37,8 @@ pub fn run_program(
     let stdout = if inherit_output { Stdio::inherit() } else { Stdio::piped() };
     let stderr = if inherit_output { Stdio::inherit() } else { Stdio::piped() };
 
-    let mut child = Command::new(input.command_name)
+    let mut child = Command::new(&executable)
+        .arg0(input.command_name)
         .args(input.command_arguments)
         .current_dir(input.current_dir)
         .stdin(stdin)
@@ -48,5 +57,5 @@ pub fn run_program(
         *previous_stdout = child.stdout.take();
     }
 
-    Ok(child)
+    Ok((child, hashable))
 }
@@ -1,16 +1,72 @@
-use crate::shell::{CommandInput, CommandOutput};
+use crate::{
+    commands::resolve,
+    shell::{CommandInput, CommandOutput},
+};
 
+const USAGE: &str = "type: usage: type [-afptP] name [name ...]";
+
+/// `type [-afptP] name...`: how each name would be run. `-a` lists every match, `-t` prints
+/// only the kind, `-p` only the path of a file, and `-P` searches the files even for builtins.
 pub fn type_fn(input: CommandInput) -> CommandOutput {
-    let Some(name) = input.command_arguments.first() else {
-        return CommandOutput::failure(": not found".to_string());
-    };
+    let mut all = false;
+    let mut kind_only = false;
+    let mut path_only = false;
+    let mut files_only = false;
 
-    if input.shell_commands.contains(name) {
-        CommandOutput::success(format!("{name} is a shell builtin"))
-    } else {
-        match input.os.find_executable(name, input.current_dir) {
-            Some(path) => CommandOutput::success(format!("{name} is {}", path.display())),
-            None => CommandOutput::failure(format!("{name}: not found")),
+    let mut names = input.command_arguments;
+    while let [option, rest @ ..] = names {
+        if option == "--" {
+            names = rest;
+            break;
+        }
+        let Some(flags) = option.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'a' => all = true,
+                // there are no functions to skip
+                'f' => {}
+                't' => kind_only = true,
+                'p' => path_only = true,
+                'P' => files_only = true,
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "type: -{flag}: invalid option\n{USAGE}"
+                    ));
+                }
+            }
         }
+        names = rest;
     }
+
+    let mut output = String::new();
+    let mut errors = Vec::new();
+    let mut found_all = true;
+
+    for name in names {
+        let resolutions = resolve::resolve(name, &input, all, files_only);
+        if resolutions.is_empty() {
+            found_all = false;
+            // like bash, only the descriptive form complains
+            if !kind_only && !path_only && !files_only {
+                errors.push(format!("{name}: not found"));
+            }
+            continue;
+        }
+
+        for resolution in &resolutions {
+            if kind_only {
+                output += format!("{}\n", resolution.kind()).as_str();
+            } else if path_only || files_only {
+                if let Some(path) = resolution.path() {
+                    output += format!("{}\n", path.display()).as_str();
+                }
+            } else {
+                output += format!("{}\n", resolution.describe(name)).as_str();
+            }
+        }
+    }
+
+    resolve::report(output, errors, found_all)
 }
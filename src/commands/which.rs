@@ -0,0 +1,55 @@
+use crate::{
+    commands::resolve,
+    shell::{CommandInput, CommandOutput},
+};
+
+const USAGE: &str = "which: usage: which [-a] name [name ...]";
+
+/// `which [-a] name...`: the path of each command, or a note that it's a builtin. `-a` lists
+/// every match instead of the one that runs.
+pub fn which(input: CommandInput) -> CommandOutput {
+    let mut all = false;
+
+    let mut names = input.command_arguments;
+    while let [option, rest @ ..] = names {
+        if option == "--" {
+            names = rest;
+            break;
+        }
+        let Some(flags) = option.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'a' => all = true,
+                _ => {
+                    return CommandOutput::failure(format!(
+                        "which: -{flag}: invalid option\n{USAGE}"
+                    ));
+                }
+            }
+        }
+        names = rest;
+    }
+
+    let mut output = String::new();
+    let mut errors = Vec::new();
+    let mut found_all = true;
+
+    for name in names {
+        let resolutions = resolve::resolve(name, &input, all, false);
+        if resolutions.is_empty() {
+            found_all = false;
+            errors.push(format!("{name} not found"));
+        }
+
+        for resolution in resolutions {
+            match resolution.path() {
+                Some(path) => output += format!("{}\n", path.display()).as_str(),
+                None => output += format!("{name}: shell built-in command\n").as_str(),
+            }
+        }
+    }
+
+    resolve::report(output, errors, found_all)
+}
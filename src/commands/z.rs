@@ -0,0 +1,85 @@
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::{
+    execute,
+    terminal::{self, ClearType},
+};
+
+use crate::{
+    commands::cd,
+    os, parser,
+    shell::{
+        CommandInput, CommandOutput, frecency,
+        menu::{self, MenuItem},
+        prompt,
+    },
+};
+
+/// `z keyword...`: jumps to the best ranked directory matching every keyword. With no keywords,
+/// `-` or the path of a directory it behaves like `cd`; `-l` lists the matches with their scores.
+pub fn z(input: CommandInput) -> CommandOutput {
+    match input.command_arguments {
+        [] => return cd(input),
+        [argument]
+            if argument == "-"
+                || parser::parse_path(argument, input.current_dir)
+                    .is_ok_and(|path| path.is_dir()) =>
+        {
+            return cd(input);
+        }
+        [flag, keywords @ ..] if flag == "-l" => {
+            let now = os::now();
+            let output: String = frecency::query(keywords, input.current_dir)
+                .iter()
+                .map(|entry| format!("{:<10.1} {}\n", entry.frecency(now), entry.path.display()))
+                .collect();
+            return CommandOutput::success(output);
+        }
+        _ => {}
+    }
+
+    match frecency::query(input.command_arguments, input.current_dir)
+        .into_iter()
+        .next()
+    {
+        Some(entry) => CommandOutput::path_update(entry.path),
+        None => CommandOutput::failure(String::from("z: no match found")),
+    }
+}
+
+/// `zi keyword...`: picks one of the matching directories from a menu, best ranked first.
+pub fn zi(input: CommandInput) -> CommandOutput {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return CommandOutput::failure(String::from("zi: not a terminal"));
+    }
+
+    let now = os::now();
+    let matches = frecency::query(input.command_arguments, input.current_dir);
+    if matches.is_empty() {
+        return CommandOutput::failure(String::from("zi: no match found"));
+    }
+    let items: Vec<MenuItem> = matches
+        .iter()
+        .map(|entry| MenuItem {
+            label: prompt::abbreviate_home(&entry.path),
+            description: Some(format!("{:.1}", entry.frecency(now))),
+        })
+        .collect();
+
+    terminal::enable_raw_mode().unwrap();
+    let choice = menu::select(&items, |selected| {
+        let mut stdout = io::stdout();
+        execute!(stdout, terminal::Clear(ClearType::CurrentLine)).unwrap();
+        print!("\rzi: {}", items[selected].label);
+        stdout.flush().unwrap();
+    });
+    execute!(io::stdout(), terminal::Clear(ClearType::CurrentLine)).unwrap();
+    print!("\r");
+    io::stdout().flush().unwrap();
+    terminal::disable_raw_mode().unwrap();
+
+    match choice {
+        Some(selected) => CommandOutput::path_update(matches[selected].path.clone()),
+        None => CommandOutput::empty(),
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::parser;
+
+/// Patterns from `.gitignore`-style files; the last matching pattern wins.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+struct Rule {
+    /// Directory of the ignore file, relative to the work tree ("" for the root).
+    base: String,
+    components: Vec<String>,
+    negated: bool,
+    dir_only: bool,
+    /// Patterns with a `/` before the end only match relative to `base`,
+    /// the others match a name at any depth.
+    anchored: bool,
+}
+
+impl IgnoreRules {
+    /// Rules that apply to the whole work tree: `info/exclude` and the user's global ignore file.
+    pub fn for_git_dir(git_dir: &Path) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        for path in global_files(git_dir) {
+            rules.add_file(&path, "");
+        }
+        rules
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Drops the rules added after the first `len` ones, when leaving a directory.
+    pub fn truncate(&mut self, len: usize) {
+        self.rules.truncate(len);
+    }
+
+    /// Adds the patterns of an ignore file found in `base` (relative to the work tree).
+    pub fn add_file(&mut self, path: &Path, base: &str) {
+        if let Ok(content) = fs::read_to_string(path) {
+            self.add_patterns(&content, base);
+        }
+    }
+
+    /// Adds the patterns of an ignore file's `content`, one per line.
+    fn add_patterns(&mut self, content: &str, base: &str) {
+        for line in content.lines() {
+            let mut pattern = line.trim_end();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+
+            let negated = pattern.starts_with('!');
+            // a leading `!` negates, `\#` and `\!` stand for the literal character
+            if negated || pattern.starts_with("\\#") || pattern.starts_with("\\!") {
+                pattern = &pattern[1..];
+            }
+
+            let dir_only = pattern.ends_with('/');
+            let pattern = pattern.trim_end_matches('/');
+            let anchored = pattern.contains('/');
+            let pattern = pattern.trim_start_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+
+            self.rules.push(Rule {
+                base: base.to_string(),
+                components: pattern.split('/').map(String::from).collect(),
+                negated,
+                dir_only,
+                anchored,
+            });
+        }
+    }
+
+    /// Whether a path relative to the work tree is ignored by the loaded rules.
+    /// Only the path itself is checked, not its parent directories.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        for rule in self.rules.iter().rev() {
+            let relative = if rule.base.is_empty() {
+                path
+            } else {
+                match path
+                    .strip_prefix(rule.base.as_str())
+                    .and_then(|p| p.strip_prefix('/'))
+                {
+                    Some(rest) => rest,
+                    None => continue,
+                }
+            };
+
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+
+            let matched = if rule.anchored {
+                let parts: Vec<&str> = relative.split('/').collect();
+                match_components(&rule.components, &parts)
+            } else {
+                let name = relative.rsplit('/').next().unwrap_or(relative);
+                parser::glob_match(&rule.components[0], name)
+            };
+
+            if matched {
+                return !rule.negated;
+            }
+        }
+
+        false
+    }
+}
+
+/// The ignore files that apply to the whole work tree, in the order their rules are added.
+pub fn global_files(git_dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| env::var("HOME").ok().map(|home| format!("{home}/.config")));
+    if let Some(config_home) = config_home {
+        files.push(Path::new(&config_home).join("git/ignore"));
+    }
+    files.push(git_dir.join("info/exclude"));
+
+    files
+}
+
+fn match_components(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.first().map(String::as_str) {
+        None => path.is_empty(),
+        // a trailing `**` matches everything inside, but not the directory itself
+        Some("**") if pattern.len() == 1 => !path.is_empty(),
+        Some("**") => {
+            match_components(&pattern[1..], path)
+                || (!path.is_empty() && match_components(pattern, &path[1..]))
+        }
+        Some(component) => {
+            !path.is_empty()
+                && parser::glob_match(component, path[0])
+                && match_components(&pattern[1..], &path[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(patterns: &[(&str, &str)]) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        for (content, base) in patterns {
+            rules.add_patterns(content, base);
+        }
+        rules
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        let rules = rules(&[("*.log\ntarget/\n# comment\n\n", "")]);
+
+        assert!(rules.is_ignored("debug.log", false));
+        assert!(rules.is_ignored("deep/inside/debug.log", false));
+        assert!(rules.is_ignored("target", true));
+        assert!(rules.is_ignored("crates/shell/target", true));
+        assert!(!rules.is_ignored("target", false));
+        assert!(!rules.is_ignored("log.txt", false));
+        assert!(!rules.is_ignored("# comment", false));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_their_base() {
+        let rules = rules(&[("/build\ndocs/*.html\n", ""), ("/generated.rs\n", "src")]);
+
+        assert!(rules.is_ignored("build", true));
+        assert!(!rules.is_ignored("src/build", true));
+        assert!(rules.is_ignored("docs/index.html", false));
+        assert!(!rules.is_ignored("docs/api/index.html", false));
+        assert!(rules.is_ignored("src/generated.rs", false));
+        assert!(!rules.is_ignored("generated.rs", false));
+        assert!(!rules.is_ignored("src/nested/generated.rs", false));
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        let rules = rules(&[("**/cache\nlogs/**\na/**/b\n", "")]);
+
+        assert!(rules.is_ignored("cache", true));
+        assert!(rules.is_ignored("x/y/cache", true));
+        assert!(rules.is_ignored("logs/today.txt", false));
+        assert!(!rules.is_ignored("logs", true));
+        assert!(rules.is_ignored("a/b", false));
+        assert!(rules.is_ignored("a/x/y/b", false));
+    }
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        let rules = rules(&[("*.txt\n!keep.txt\n\\!bang\n", ""), ("keep.txt\n", "sub")]);
+
+        assert!(rules.is_ignored("notes.txt", false));
+        assert!(!rules.is_ignored("keep.txt", false));
+        assert!(rules.is_ignored("sub/keep.txt", false));
+        assert!(rules.is_ignored("!bang", false));
+    }
+
+    #[test]
+    fn truncate_drops_rules_of_a_directory() {
+        let mut rules = rules(&[("*.o\n", "")]);
+        let len = rules.len();
+        rules.add_patterns("*.tmp\n", "sub");
+        assert!(rules.is_ignored("sub/a.tmp", false));
+
+        rules.truncate(len);
+        assert!(!rules.is_ignored("sub/a.tmp", false));
+        assert!(rules.is_ignored("sub/a.o", false));
+    }
+}
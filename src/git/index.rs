@@ -0,0 +1,185 @@
+use std::{fs, path::Path};
+
+use crate::git::objects::{Oid, read_u32};
+
+pub struct IndexEntry {
+    pub path: String,
+    pub oid: Oid,
+    pub mode: u32,
+    pub size: u32,
+    pub mtime_seconds: u32,
+    pub mtime_nanoseconds: u32,
+    /// Non-zero while a merge conflict is unresolved (1 base, 2 ours, 3 theirs).
+    pub stage: u8,
+}
+
+/// Reads the entries of `.git/index` (versions 2, 3 and 4).
+pub fn read_index(path: &Path) -> Option<Vec<IndexEntry>> {
+    parse_index(&fs::read(path).ok()?)
+}
+
+fn parse_index(data: &[u8]) -> Option<Vec<IndexEntry>> {
+    if !data.starts_with(b"DIRC") {
+        return None;
+    }
+
+    let version = read_u32(data, 4)?;
+    let count = read_u32(data, 8)? as usize;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+
+    let mut entries: Vec<IndexEntry> = Vec::with_capacity(count);
+    let mut position = 12;
+    let mut previous_path: Vec<u8> = Vec::new();
+
+    for _ in 0..count {
+        let entry_start = position;
+        let mtime_seconds = read_u32(data, position + 8)?;
+        let mtime_nanoseconds = read_u32(data, position + 12)?;
+        let mode = read_u32(data, position + 24)?;
+        let size = read_u32(data, position + 36)?;
+        let oid: Oid = data.get(position + 40..position + 60)?.try_into().ok()?;
+        let flags = u16::from_be_bytes(data.get(position + 60..position + 62)?.try_into().ok()?);
+        position += 62;
+
+        // version 3+ entries may carry a second flags word
+        if version >= 3 && flags & 0x4000 != 0 {
+            data.get(position..position + 2)?;
+            position += 2;
+        }
+
+        let path_bytes = if version == 4 {
+            // prefix compression: drop N bytes from the previous path, then append the suffix
+            let mut byte = *data.get(position)?;
+            let mut strip = (byte & 0x7f) as usize;
+            while byte & 0x80 != 0 {
+                position += 1;
+                byte = *data.get(position)?;
+                strip = ((strip + 1) << 7) | (byte & 0x7f) as usize;
+            }
+            position += 1;
+
+            // the prefix is counted in bytes, which may split a multi-byte character
+            let nul = data.get(position..)?.iter().position(|&b| b == 0)?;
+            let mut path = previous_path[..previous_path.len().checked_sub(strip)?].to_vec();
+            path.extend_from_slice(&data[position..position + nul]);
+            position += nul + 1;
+            path
+        } else {
+            let nul = data.get(position..)?.iter().position(|&b| b == 0)?;
+            let path = data[position..position + nul].to_vec();
+            // entries are NUL padded to a multiple of 8 bytes
+            let length = position + nul - entry_start;
+            position = entry_start + (length + 8) / 8 * 8;
+            path
+        };
+
+        let path = String::from_utf8_lossy(&path_bytes).to_string();
+        previous_path = path_bytes;
+        entries.push(IndexEntry {
+            path,
+            oid,
+            mode,
+            size,
+            mtime_seconds,
+            mtime_nanoseconds,
+            stage: ((flags >> 12) & 3) as u8,
+        });
+    }
+
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index of the given version holding `entries`, each a path (already prefix
+    /// compressed for version 4) and whether it carries extended flags.
+    fn build_index(version: u32, entries: &[(&[u8], bool)]) -> Vec<u8> {
+        let mut data = b"DIRC".to_vec();
+        data.extend_from_slice(&version.to_be_bytes());
+        data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+
+        for (number, (path, extended)) in entries.iter().enumerate() {
+            let entry_start = data.len();
+            let mut header = [0u8; 62];
+            header[8..12].copy_from_slice(&1_700_000_000u32.to_be_bytes());
+            header[24..28].copy_from_slice(&0o100644u32.to_be_bytes());
+            header[36..40].copy_from_slice(&(number as u32 + 10).to_be_bytes());
+            header[40..60].copy_from_slice(&[number as u8; 20]);
+            let flags: u16 = if *extended { 0x4000 } else { 0 };
+            header[60..62].copy_from_slice(&flags.to_be_bytes());
+            data.extend_from_slice(&header);
+            if *extended {
+                data.extend_from_slice(&[0, 0]);
+            }
+
+            data.extend_from_slice(path);
+            data.push(0);
+            if version < 4 {
+                while !(data.len() - entry_start).is_multiple_of(8) {
+                    data.push(0);
+                }
+            }
+        }
+
+        data
+    }
+
+    fn paths(entries: &[IndexEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.path.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_version_2() {
+        let data = build_index(2, &[(b"README.md", false), (b"src/main.rs", false)]);
+        let entries = parse_index(&data).unwrap();
+
+        assert_eq!(paths(&entries), ["README.md", "src/main.rs"]);
+        assert_eq!(entries[1].mode, 0o100644);
+        assert_eq!(entries[1].size, 11);
+        assert_eq!(entries[1].oid, [1; 20]);
+        assert_eq!(entries[0].mtime_seconds, 1_700_000_000);
+        assert_eq!(entries[0].stage, 0);
+    }
+
+    #[test]
+    fn reads_version_3_extended_flags() {
+        let data = build_index(3, &[(b"a.txt", true), (b"b.txt", false), (b"c.txt", true)]);
+        let entries = parse_index(&data).unwrap();
+
+        assert_eq!(paths(&entries), ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(entries[2].oid, [2; 20]);
+    }
+
+    #[test]
+    fn reads_version_4_prefix_compression() {
+        let data = build_index(
+            4,
+            &[(b"\x00src/lib.rs", false), (b"\x06main.rs", false), (b"\x0bREADME", false)],
+        );
+        let entries = parse_index(&data).unwrap();
+
+        assert_eq!(paths(&entries), ["src/lib.rs", "src/main.rs", "README"]);
+    }
+
+    #[test]
+    fn version_4_prefix_can_split_a_character() {
+        // "cafè" shares "caf\xc3" with "café", so only the last byte of the `é` is dropped
+        let data = build_index(4, &[("\x00café".as_bytes(), false), (b"\x01\xa8", false)]);
+        let entries = parse_index(&data).unwrap();
+
+        assert_eq!(paths(&entries), ["café", "cafè"]);
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let data = build_index(3, &[(b"a.txt", true)]);
+
+        assert!(parse_index(&data[..70]).is_none());
+        assert!(parse_index(&data[..12]).is_none());
+        assert!(parse_index(b"DIRC\x00\x00\x00\x05\x00\x00\x00\x00").is_none());
+    }
+}
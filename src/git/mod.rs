@@ -0,0 +1,846 @@
+mod ignore;
+mod index;
+mod objects;
+
+use std::{
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet},
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+pub use crate::git::ignore::IgnoreRules;
+use crate::git::{
+    index::IndexEntry,
+    objects::{Commit, ObjectStore, Oid},
+};
+
+/// Commits visited at most when counting ahead/behind, so huge histories can't stall the prompt.
+const MAX_WALKED_COMMITS: usize = 10_000;
+
+const MODE_TREE: u32 = 0o040000;
+const MODE_SYMLINK: u32 = 0o120000;
+const MODE_GITLINK: u32 = 0o160000;
+
+/// Files of a tree by path, with their mode and blob id.
+type TreeFiles = HashMap<String, (u32, Oid)>;
+/// Directories and ignore files with the mtime they had when they were read.
+type DirSnapshot = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// A work tree and the `.git` directory behind it, found by walking up from a directory.
+pub struct Repository {
+    pub work_tree: PathBuf,
+    pub git_dir: PathBuf,
+    /// Where refs and objects live; differs from `git_dir` in linked work trees.
+    common_dir: PathBuf,
+}
+
+pub enum Head {
+    Branch(String),
+    Detached(Oid),
+}
+
+/// Changes between HEAD, the index and the work tree, as paths relative to the work tree.
+#[derive(Clone, Default)]
+pub struct Status {
+    pub staged: BTreeSet<String>,
+    pub modified: BTreeSet<String>,
+    /// Untracked directories are listed once, with a trailing `/`, except in `dir` listings.
+    pub untracked: BTreeSet<String>,
+    pub conflicted: BTreeSet<String>,
+}
+
+/// What the prompt shows about the repository.
+pub struct Summary {
+    pub head: Head,
+    pub status: Status,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl Summary {
+    /// Compact form for the prompt, e.g. `main *+% ↑1↓2`: `*` unstaged changes, `+` staged
+    /// changes, `%` untracked files, `!` conflicts. A detached HEAD shows its short id.
+    pub fn describe(&self) -> String {
+        let mut text = match &self.head {
+            Head::Branch(name) => name.clone(),
+            Head::Detached(oid) => objects::to_hex(oid)[..7].to_string(),
+        };
+
+        let mut markers = String::new();
+        if !self.status.modified.is_empty() {
+            markers.push('*');
+        }
+        if !self.status.staged.is_empty() {
+            markers.push('+');
+        }
+        if !self.status.untracked.is_empty() {
+            markers.push('%');
+        }
+        if !self.status.conflicted.is_empty() {
+            markers.push('!');
+        }
+        if !markers.is_empty() {
+            text.push(' ');
+            text.push_str(&markers);
+        }
+
+        if self.ahead > 0 || self.behind > 0 {
+            text.push(' ');
+        }
+        if self.ahead > 0 {
+            text.push_str(&format!("↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            text.push_str(&format!("↓{}", self.behind));
+        }
+
+        text
+    }
+}
+
+/// Git state of the files under one directory of a work tree, shared by the listings of that
+/// directory and of its subdirectories.
+struct TreeStatus {
+    work_tree: PathBuf,
+    /// Canonical path of the directory.
+    root: PathBuf,
+    status: Status,
+    /// Index paths under the directory, with the directories holding them.
+    tracked: HashSet<String>,
+    /// Paths excluded by ignore rules; what's inside them is ignored too, unless tracked.
+    ignored: HashSet<String>,
+}
+
+/// Git state of the entries of one directory, for `dir` listings.
+pub struct DirectoryStatus {
+    /// The directory, relative to the work tree ("" for its root).
+    prefix: String,
+    tree: Rc<TreeStatus>,
+}
+
+impl DirectoryStatus {
+    /// Markers of an entry of the directory, using the prompt's symbols: `*` modified, `+` staged,
+    /// `%` untracked, `!` conflicted, plus `#` ignored. A directory shows the markers of
+    /// everything below it.
+    pub fn markers(&self, name: &str) -> String {
+        if name == "." || name == ".." {
+            return String::new();
+        }
+        let path = if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{name}", self.prefix)
+        };
+        let inside = format!("{path}/");
+        let contains = |set: &BTreeSet<String>| {
+            set.contains(&path)
+                || set
+                    .range(inside.clone()..)
+                    .next()
+                    .is_some_and(|p| p.starts_with(&inside))
+        };
+
+        let status = &self.tree.status;
+        let mut markers = String::new();
+        if contains(&status.modified) {
+            markers.push('*');
+        }
+        if contains(&status.staged) {
+            markers.push('+');
+        }
+        if contains(&status.untracked) {
+            markers.push('%');
+        }
+        if contains(&status.conflicted) {
+            markers.push('!');
+        }
+        if self.is_ignored(&path) {
+            markers.push('#');
+        }
+        markers
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        let ignored = &self.tree.ignored;
+        !self.tree.tracked.contains(path)
+            && (ignored.contains(path)
+                || path
+                    .match_indices('/')
+                    .any(|(position, _)| ignored.contains(&path[..position])))
+    }
+}
+
+/// Directory statuses read during one `dir` call. A subdirectory of a directory already read
+/// reuses its status, so `dir -R` reads each part of a work tree once.
+#[derive(Default)]
+pub struct StatusCache {
+    trees: Vec<Rc<TreeStatus>>,
+}
+
+impl StatusCache {
+    pub fn directory_status(&mut self, dir: &Path) -> Option<DirectoryStatus> {
+        let repository = Repository::discover(dir)?;
+        let work_tree = repository.work_tree.canonicalize().ok()?;
+        let dir = dir.canonicalize().ok()?;
+        let prefix = dir.strip_prefix(&work_tree).ok()?.to_string_lossy().to_string();
+        if prefix == ".git" || prefix.starts_with(".git/") {
+            return None;
+        }
+
+        let cached = self
+            .trees
+            .iter()
+            .find(|tree| tree.work_tree == work_tree && dir.starts_with(&tree.root))
+            .cloned();
+        let tree = match cached {
+            Some(tree) => tree,
+            None => {
+                let tree = Rc::new(tree_status(&repository, work_tree, dir, &prefix));
+                self.trees.push(tree.clone());
+                tree
+            }
+        };
+
+        Some(DirectoryStatus { prefix, tree })
+    }
+}
+
+impl Repository {
+    pub fn discover(start: &Path) -> Option<Repository> {
+        for dir in start.ancestors() {
+            let dot_git = dir.join(".git");
+
+            if dot_git.is_dir() {
+                return Some(Repository::open(dir, dot_git));
+            }
+
+            // work trees and submodules point to their git directory with a "gitdir: " file
+            if dot_git.is_file()
+                && let Ok(content) = fs::read_to_string(&dot_git)
+                && let Some(git_dir) = content.trim().strip_prefix("gitdir: ")
+            {
+                return Some(Repository::open(dir, dir.join(git_dir)));
+            }
+        }
+
+        None
+    }
+
+    fn open(work_tree: &Path, git_dir: PathBuf) -> Repository {
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(content) => git_dir.join(content.trim()),
+            Err(_) => git_dir.clone(),
+        };
+
+        Repository {
+            work_tree: work_tree.to_path_buf(),
+            git_dir,
+            common_dir,
+        }
+    }
+
+    pub fn head(&self) -> Option<(Head, Option<Oid>)> {
+        let content = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+
+        match content.trim().strip_prefix("ref: ") {
+            Some(reference) => {
+                let name = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+                Some((Head::Branch(name.to_string()), self.resolve_ref(reference)))
+            }
+            None => {
+                let oid = objects::parse_hex(&content)?;
+                Some((Head::Detached(oid), Some(oid)))
+            }
+        }
+    }
+
+    fn resolve_ref(&self, name: &str) -> Option<Oid> {
+        let mut name = name.to_string();
+
+        // follow symbolic refs a few levels deep
+        for _ in 0..5 {
+            let base = if name.starts_with("refs/") {
+                &self.common_dir
+            } else {
+                &self.git_dir
+            };
+
+            match fs::read_to_string(base.join(&name)) {
+                Ok(content) => match content.trim().strip_prefix("ref: ") {
+                    Some(target) => name = target.to_string(),
+                    None => return objects::parse_hex(&content),
+                },
+                Err(_) => break,
+            }
+        }
+
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed.lines().find_map(|line| {
+            let (hex, reference) = line.split_once(' ')?;
+            if reference == name {
+                objects::parse_hex(hex)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Remote-tracking ref of a branch, from its `branch.<name>.remote`/`merge` config.
+    fn upstream(&self, branch: &str) -> Option<String> {
+        let remote = self.config_value(&format!("branch \"{branch}\""), "remote")?;
+        let merge = self.config_value(&format!("branch \"{branch}\""), "merge")?;
+        let merge = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+
+        if remote == "." {
+            return Some(format!("refs/heads/{merge}"));
+        }
+        Some(format!("refs/remotes/{remote}/{merge}"))
+    }
+
+    fn config_value(&self, section: &str, key: &str) -> Option<String> {
+        let content = fs::read_to_string(self.common_dir.join("config")).ok()?;
+        let mut in_section = false;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_section = header == section;
+            } else if in_section
+                && let Some((name, value)) = line.split_once('=')
+                && name.trim().eq_ignore_ascii_case(key)
+            {
+                return Some(value.trim().to_string());
+            }
+        }
+
+        None
+    }
+
+    fn objects(&self) -> ObjectStore {
+        ObjectStore::open(&self.common_dir.join("objects"))
+    }
+}
+
+/// Keeps what was read from each repository, so the prompt only redoes work when
+/// the index, HEAD, work tree files or directories have a new mtime.
+#[derive(Default)]
+pub struct GitCache {
+    repos: HashMap<PathBuf, RepoCache>,
+}
+
+#[derive(Default)]
+struct RepoCache {
+    index: Option<(Option<SystemTime>, Vec<IndexEntry>)>,
+    head_tree: Option<(Option<Oid>, TreeFiles)>,
+    /// Blob ids of modified work tree files, by path, valid for the (mtime, size) they had.
+    hashes: HashMap<String, (Option<SystemTime>, u64, Oid)>,
+    /// Untracked paths, valid while none of the recorded directory mtimes change.
+    untracked: Option<(DirSnapshot, BTreeSet<String>)>,
+    ahead_behind: Option<((Oid, Oid), (usize, usize))>,
+}
+
+impl GitCache {
+    /// Branch and status of the repository containing `dir`, if any.
+    pub fn summary(&mut self, dir: &Path) -> Option<Summary> {
+        let repository = Repository::discover(dir)?;
+        let (head, head_oid) = repository.head()?;
+
+        let cache = self.repos.entry(repository.work_tree.clone()).or_default();
+        let store = repository.objects();
+        let status = cache.status(&repository, &store, head_oid);
+
+        let mut ahead_behind = (0, 0);
+        if let Head::Branch(name) = &head
+            && let Some(local) = head_oid
+            && let Some(upstream) = repository
+                .upstream(name)
+                .and_then(|reference| repository.resolve_ref(&reference))
+        {
+            ahead_behind = match cache.ahead_behind {
+                Some((key, counts)) if key == (local, upstream) => counts,
+                _ => {
+                    let counts = count_ahead_behind(&store, local, upstream);
+                    cache.ahead_behind = Some(((local, upstream), counts));
+                    counts
+                }
+            };
+        }
+
+        Some(Summary {
+            head,
+            status,
+            ahead: ahead_behind.0,
+            behind: ahead_behind.1,
+        })
+    }
+}
+
+impl RepoCache {
+    fn status(
+        &mut self,
+        repository: &Repository,
+        store: &ObjectStore,
+        head_oid: Option<Oid>,
+    ) -> Status {
+        let index_path = repository.git_dir.join("index");
+        let index_mtime = modified_time(&index_path);
+        if !matches!(&self.index, Some((mtime, _)) if *mtime == index_mtime) {
+            let entries = index::read_index(&index_path).unwrap_or_default();
+            self.index = Some((index_mtime, entries));
+        }
+
+        if !matches!(&self.head_tree, Some((oid, _)) if *oid == head_oid) {
+            let mut tree = HashMap::new();
+            if let Some(commit) = head_oid.and_then(|oid| store.read_commit(&oid)) {
+                flatten_tree(store, &commit.tree, "", &mut tree);
+            }
+            self.head_tree = Some((head_oid, tree));
+        }
+
+        let entries = self
+            .index
+            .as_ref()
+            .map(|(_, e)| e.as_slice())
+            .unwrap_or_default();
+        let head_tree = self.head_tree.as_ref().map(|(_, t)| t);
+        let mut status = Status::default();
+
+        // HEAD against the index
+        let mut in_index: HashSet<&str> = HashSet::new();
+        for entry in entries {
+            in_index.insert(&entry.path);
+
+            if entry.stage != 0 {
+                status.conflicted.insert(entry.path.clone());
+                continue;
+            }
+
+            let in_head = head_tree.and_then(|tree| tree.get(&entry.path));
+            if in_head != Some(&(entry.mode, entry.oid)) {
+                status.staged.insert(entry.path.clone());
+            }
+        }
+        if let Some(tree) = head_tree {
+            for path in tree.keys() {
+                if !in_index.contains(path.as_str()) {
+                    status.staged.insert(path.clone());
+                }
+            }
+        }
+
+        // the index against the work tree
+        for entry in entries {
+            if entry.stage == 0 && is_modified(&mut self.hashes, &repository.work_tree, entry) {
+                status.modified.insert(entry.path.clone());
+            }
+        }
+
+        status.untracked = untracked(&mut self.untracked, repository, entries);
+        status
+    }
+}
+
+/// Whether a work tree file differs from its index entry, hashing it only when its stat data changed.
+fn is_modified(
+    hashes: &mut HashMap<String, (Option<SystemTime>, u64, Oid)>,
+    work_tree: &Path,
+    entry: &IndexEntry,
+) -> bool {
+    if entry.mode == MODE_GITLINK {
+        return false;
+    }
+
+    let path = work_tree.join(&entry.path);
+    let Ok(metadata) = fs::symlink_metadata(&path) else {
+        return true;
+    };
+
+    let is_symlink = metadata.file_type().is_symlink();
+    if is_symlink != (entry.mode == MODE_SYMLINK) || metadata.is_dir() {
+        return true;
+    }
+    if !is_symlink && (metadata.permissions().mode() & 0o111 != 0) != (entry.mode & 0o111 != 0) {
+        return true;
+    }
+    // the index keeps the size truncated to 32 bits
+    if metadata.size() as u32 != entry.size {
+        return true;
+    }
+    if metadata.mtime() as u32 == entry.mtime_seconds
+        && metadata.mtime_nsec() as u32 == entry.mtime_nanoseconds
+    {
+        return false;
+    }
+
+    // same size but touched since the index was written: compare the content
+    let mtime = metadata.modified().ok();
+    let oid = match hashes.get(&entry.path) {
+        Some((cached_mtime, size, oid)) if *cached_mtime == mtime && *size == metadata.size() => {
+            *oid
+        }
+        _ => {
+            let content = if is_symlink {
+                fs::read_link(&path)
+                    .map(|target| target.as_os_str().as_encoded_bytes().to_vec())
+                    .unwrap_or_default()
+            } else {
+                fs::read(&path).unwrap_or_default()
+            };
+            let oid = objects::hash_blob(&content);
+            hashes.insert(entry.path.clone(), (mtime, metadata.size(), oid));
+            oid
+        }
+    };
+
+    oid != entry.oid
+}
+
+/// Status of the files under one directory of a work tree. Only that part of the index, the HEAD
+/// tree and the work tree is read, so listings stay fast in large repositories.
+fn tree_status(
+    repository: &Repository,
+    work_tree: PathBuf,
+    dir: PathBuf,
+    prefix: &str,
+) -> TreeStatus {
+    let relative = Path::new(prefix);
+    let inside = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{prefix}/")
+    };
+
+    let entries = index::read_index(&repository.git_dir.join("index")).unwrap_or_default();
+    let under_dir: Vec<&IndexEntry> = entries
+        .iter()
+        .filter(|entry| entry.path.starts_with(&inside))
+        .collect();
+
+    // the HEAD tree of the directory only
+    let store = repository.objects();
+    let mut head_tree = HashMap::new();
+    let mut tree_oid = repository
+        .head()
+        .and_then(|(_, oid)| oid)
+        .and_then(|oid| store.read_commit(&oid))
+        .map(|commit| commit.tree);
+    for component in relative.components() {
+        let name = component.as_os_str().to_string_lossy();
+        tree_oid = tree_oid
+            .and_then(|oid| store.read_tree(&oid))
+            .and_then(|tree| tree.into_iter().find(|e| e.name == name && e.mode == MODE_TREE))
+            .map(|entry| entry.oid);
+    }
+    if let Some(oid) = tree_oid {
+        flatten_tree(&store, &oid, &inside, &mut head_tree);
+    }
+
+    let mut status = Status::default();
+    let mut hashes = HashMap::new();
+    for entry in &under_dir {
+        if entry.stage != 0 {
+            status.conflicted.insert(entry.path.clone());
+            continue;
+        }
+        if head_tree.get(&entry.path) != Some(&(entry.mode, entry.oid)) {
+            status.staged.insert(entry.path.clone());
+        }
+        if is_modified(&mut hashes, &repository.work_tree, entry) {
+            status.modified.insert(entry.path.clone());
+        }
+    }
+    let in_index: HashSet<&str> = under_dir.iter().map(|entry| entry.path.as_str()).collect();
+    for path in head_tree.keys() {
+        if !in_index.contains(path.as_str()) {
+            status.staged.insert(path.clone());
+        }
+    }
+
+    let mut walk = UntrackedWalk {
+        work_tree: &repository.work_tree,
+        tracked: HashSet::new(),
+        tracked_dirs: HashSet::new(),
+        rules: IgnoreRules::for_git_dir(&repository.git_dir),
+        found: BTreeSet::new(),
+        ignored: HashSet::new(),
+        // subdirectories are listed too, so every untracked file is needed
+        collapse: false,
+        snapshot: Vec::new(),
+    };
+    for entry in &entries {
+        walk.tracked.insert(&entry.path);
+        let mut current = entry.path.as_str();
+        while let Some((parent, _)) = current.rsplit_once('/') {
+            if !walk.tracked_dirs.insert(parent) {
+                break;
+            }
+            current = parent;
+        }
+    }
+    // rules of the parent directories, then the directory's own ones while walking it
+    let mut base = String::new();
+    for component in relative.components() {
+        walk.rules.add_file(&repository.work_tree.join(&base).join(".gitignore"), &base);
+        let name = component.as_os_str().to_string_lossy();
+        base = if base.is_empty() {
+            name.to_string()
+        } else {
+            format!("{base}/{name}")
+        };
+    }
+    // inside an ignored directory, everything that isn't tracked is ignored
+    if !prefix.is_empty() && walk.rules.is_ignored(prefix, true) {
+        walk.ignored.insert(prefix.to_string());
+    } else {
+        walk.visit(prefix);
+    }
+
+    let mut tracked: HashSet<String> = HashSet::new();
+    for path in walk.tracked.iter().chain(&walk.tracked_dirs) {
+        if path.starts_with(&inside) {
+            tracked.insert(path.to_string());
+        }
+    }
+    status.untracked = walk.found;
+
+    TreeStatus {
+        work_tree,
+        root: dir,
+        status,
+        tracked,
+        ignored: walk.ignored,
+    }
+}
+
+fn untracked(
+    cache: &mut Option<(DirSnapshot, BTreeSet<String>)>,
+    repository: &Repository,
+    entries: &[IndexEntry],
+) -> BTreeSet<String> {
+    if let Some((snapshot, untracked)) = cache
+        && snapshot
+            .iter()
+            .all(|(path, mtime)| modified_time(path) == *mtime)
+    {
+        return untracked.clone();
+    }
+
+    let tracked: HashSet<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+    let mut tracked_dirs: HashSet<&str> = HashSet::new();
+    for path in &tracked {
+        let mut current = *path;
+        while let Some((parent, _)) = current.rsplit_once('/') {
+            if !tracked_dirs.insert(parent) {
+                break;
+            }
+            current = parent;
+        }
+    }
+
+    let mut walk = UntrackedWalk {
+        work_tree: &repository.work_tree,
+        tracked,
+        tracked_dirs,
+        rules: IgnoreRules::for_git_dir(&repository.git_dir),
+        found: BTreeSet::new(),
+        ignored: HashSet::new(),
+        collapse: true,
+        snapshot: Vec::new(),
+    };
+    // the index mtime changes whenever files start or stop being tracked, and editing an
+    // ignore file in place doesn't change the mtime of its directory
+    for path in [repository.git_dir.join("index")]
+        .into_iter()
+        .chain(ignore::global_files(&repository.git_dir))
+    {
+        walk.snapshot.push((path.clone(), modified_time(&path)));
+    }
+    walk.visit("");
+
+    *cache = Some((walk.snapshot, walk.found.clone()));
+    walk.found
+}
+
+struct UntrackedWalk<'a> {
+    work_tree: &'a Path,
+    tracked: HashSet<&'a str>,
+    tracked_dirs: HashSet<&'a str>,
+    rules: IgnoreRules,
+    found: BTreeSet<String>,
+    /// Untracked paths skipped because an ignore rule excludes them.
+    ignored: HashSet<String>,
+    /// Whether a directory without tracked files is reported as a whole, as git does, instead
+    /// of listing every untracked file in it.
+    collapse: bool,
+    snapshot: DirSnapshot,
+}
+
+impl UntrackedWalk<'_> {
+    fn visit(&mut self, dir: &str) {
+        let full_path = self.work_tree.join(dir);
+        self.snapshot
+            .push((full_path.clone(), modified_time(&full_path)));
+
+        let saved_rules = self.rules.len();
+        self.add_ignore_file(&full_path, dir);
+
+        for (name, is_dir) in list_dir(&full_path) {
+            let path = if dir.is_empty() {
+                name
+            } else {
+                format!("{dir}/{name}")
+            };
+
+            if self.tracked.contains(path.as_str()) {
+                continue;
+            }
+            if self.rules.is_ignored(&path, is_dir) {
+                self.ignored.insert(path);
+                continue;
+            }
+
+            if !is_dir {
+                self.found.insert(path);
+            } else if self.tracked_dirs.contains(path.as_str()) || !self.collapse {
+                self.visit(&path);
+            } else if self.has_files(&path) {
+                // like git, a directory without tracked files is reported as a whole
+                self.found.insert(format!("{path}/"));
+            }
+        }
+
+        self.rules.truncate(saved_rules);
+    }
+
+    /// Loads the `.gitignore` of a directory and remembers its mtime.
+    fn add_ignore_file(&mut self, full_path: &Path, dir: &str) {
+        let path = full_path.join(".gitignore");
+        self.snapshot.push((path.clone(), modified_time(&path)));
+        self.rules.add_file(&path, dir);
+    }
+
+    /// Whether a directory holds at least one file that isn't ignored.
+    fn has_files(&mut self, dir: &str) -> bool {
+        let full_path = self.work_tree.join(dir);
+        self.snapshot
+            .push((full_path.clone(), modified_time(&full_path)));
+
+        let saved_rules = self.rules.len();
+        self.add_ignore_file(&full_path, dir);
+
+        let found = list_dir(&full_path).into_iter().any(|(name, is_dir)| {
+            let path = format!("{dir}/{name}");
+            !self.rules.is_ignored(&path, is_dir) && (!is_dir || self.has_files(&path))
+        });
+
+        self.rules.truncate(saved_rules);
+        found
+    }
+}
+
+/// Entries of a directory (without `.git`), with whether each one is a directory.
+/// Symbolic links are never followed, as git tracks the link itself.
+fn list_dir(path: &Path) -> Vec<(String, bool)> {
+    let Ok(read_dir_value) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    read_dir_value
+        .flatten()
+        .filter(|entry| entry.file_name() != ".git")
+        .map(|entry| {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            (entry.file_name().to_string_lossy().to_string(), is_dir)
+        })
+        .collect()
+}
+
+fn flatten_tree(
+    store: &ObjectStore,
+    oid: &Oid,
+    prefix: &str,
+    files: &mut TreeFiles,
+) {
+    let Some(entries) = store.read_tree(oid) else {
+        return;
+    };
+
+    for entry in entries {
+        let path = format!("{prefix}{}", entry.name);
+        if entry.mode == MODE_TREE {
+            flatten_tree(store, &entry.oid, &format!("{path}/"), files);
+        } else {
+            files.insert(path, (entry.mode, entry.oid));
+        }
+    }
+}
+
+/// Commits only reachable from `local` and only reachable from `upstream`, walking both
+/// histories newest first until everything left to visit is shared.
+fn count_ahead_behind(store: &ObjectStore, local: Oid, upstream: Oid) -> (usize, usize) {
+    const LOCAL: u8 = 1;
+    const UPSTREAM: u8 = 2;
+    const BOTH: u8 = LOCAL | UPSTREAM;
+
+    if local == upstream {
+        return (0, 0);
+    }
+
+    let mut commits: HashMap<Oid, Commit> = HashMap::new();
+    let mut flags: HashMap<Oid, u8> = HashMap::new();
+    let mut queue: BinaryHeap<(i64, Oid)> = BinaryHeap::new();
+
+    for (oid, flag) in [(local, LOCAL), (upstream, UPSTREAM)] {
+        let Some(commit) = store.read_commit(&oid) else {
+            return (0, 0);
+        };
+        queue.push((commit.time, oid));
+        flags.insert(oid, flag);
+        commits.insert(oid, commit);
+    }
+
+    let (mut ahead, mut behind) = (0, 0);
+    let mut walked = 0;
+
+    while let Some((_, oid)) = queue.pop() {
+        walked += 1;
+        if walked > MAX_WALKED_COMMITS {
+            break;
+        }
+
+        let flag = flags[&oid];
+        match flag {
+            LOCAL => ahead += 1,
+            UPSTREAM => behind += 1,
+            _ => {}
+        }
+
+        let parents = commits
+            .get(&oid)
+            .map(|c| c.parents.clone())
+            .unwrap_or_default();
+        for parent in parents {
+            let parent_flag = flags.entry(parent).or_insert(0);
+            let first_visit = *parent_flag == 0;
+            *parent_flag |= flag;
+
+            if first_visit && let Some(commit) = store.read_commit(&parent) {
+                queue.push((commit.time, parent));
+                commits.insert(parent, commit);
+            }
+        }
+
+        if queue.iter().all(|(_, oid)| flags[oid] == BOTH) {
+            break;
+        }
+    }
+
+    (ahead, behind)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,434 @@
+use std::{
+    fs::{self, File},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use miniz_oxide::inflate;
+
+pub type Oid = [u8; 20];
+
+/// Deltas deeper than this are treated as corrupt instead of recursing forever.
+const MAX_DELTA_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+pub struct Commit {
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    pub time: i64,
+}
+
+pub struct TreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub oid: Oid,
+}
+
+/// Reads objects from `.git/objects`, both loose and packed.
+pub struct ObjectStore {
+    objects_dir: PathBuf,
+    packs: Vec<Pack>,
+}
+
+struct Pack {
+    path: PathBuf,
+    index: Vec<u8>,
+}
+
+impl ObjectStore {
+    pub fn open(objects_dir: &Path) -> ObjectStore {
+        let mut packs = Vec::new();
+
+        if let Ok(read_dir_value) = fs::read_dir(objects_dir.join("pack")) {
+            for entry in read_dir_value.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "idx")
+                    && let Ok(index) = fs::read(&path)
+                    && index.starts_with(b"\xfftOc\x00\x00\x00\x02")
+                {
+                    packs.push(Pack {
+                        path: path.with_extension("pack"),
+                        index,
+                    });
+                }
+            }
+        }
+
+        ObjectStore {
+            objects_dir: objects_dir.to_path_buf(),
+            packs,
+        }
+    }
+
+    pub fn read(&self, oid: &Oid) -> Option<(ObjectKind, Vec<u8>)> {
+        self.read_with_depth(oid, 0)
+    }
+
+    pub fn read_commit(&self, oid: &Oid) -> Option<Commit> {
+        match self.read(oid)? {
+            (ObjectKind::Commit, data) => parse_commit(&data),
+            _ => None,
+        }
+    }
+
+    pub fn read_tree(&self, oid: &Oid) -> Option<Vec<TreeEntry>> {
+        match self.read(oid)? {
+            (ObjectKind::Tree, data) => Some(parse_tree(&data)),
+            _ => None,
+        }
+    }
+
+    fn read_with_depth(&self, oid: &Oid, depth: usize) -> Option<(ObjectKind, Vec<u8>)> {
+        if let Some(object) = self.read_loose(oid) {
+            return Some(object);
+        }
+
+        self.packs.iter().find_map(|pack| {
+            let offset = pack.find(oid)?;
+            self.read_packed(pack, offset, depth)
+        })
+    }
+
+    fn read_loose(&self, oid: &Oid) -> Option<(ObjectKind, Vec<u8>)> {
+        let hex = to_hex(oid);
+        let compressed = fs::read(self.objects_dir.join(&hex[..2]).join(&hex[2..])).ok()?;
+        let raw = inflate::decompress_to_vec_zlib(&compressed).ok()?;
+
+        let header_end = raw.iter().position(|&b| b == 0)?;
+        let header = std::str::from_utf8(&raw[..header_end]).ok()?;
+        let kind = match header.split(' ').next()? {
+            "commit" => ObjectKind::Commit,
+            "tree" => ObjectKind::Tree,
+            "blob" => ObjectKind::Blob,
+            "tag" => ObjectKind::Tag,
+            _ => return None,
+        };
+
+        Some((kind, raw[header_end + 1..].to_vec()))
+    }
+
+    fn read_packed(&self, pack: &Pack, offset: u64, depth: usize) -> Option<(ObjectKind, Vec<u8>)> {
+        if depth > MAX_DELTA_DEPTH {
+            return None;
+        }
+
+        let file = File::open(&pack.path).ok()?;
+        let mut header = [0u8; 32];
+        let read = file.read_at(&mut header, offset).ok()?;
+        let header = &header[..read];
+
+        // type and size: 3 bits of type, then the size in little-endian groups of 7 bits
+        let mut position = 0;
+        let mut byte = *header.get(position)?;
+        let object_type = (byte >> 4) & 7;
+        let mut size = (byte & 15) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            position += 1;
+            byte = *header.get(position)?;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+        position += 1;
+
+        match object_type {
+            1..=4 => {
+                let data = inflate_at(&file, offset + position as u64, size)?;
+                let kind = match object_type {
+                    1 => ObjectKind::Commit,
+                    2 => ObjectKind::Tree,
+                    3 => ObjectKind::Blob,
+                    _ => ObjectKind::Tag,
+                };
+                Some((kind, data))
+            }
+            6 => {
+                // offset delta: the base is stored earlier in the same pack
+                let mut byte = *header.get(position)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    position += 1;
+                    byte = *header.get(position)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                position += 1;
+
+                let delta = inflate_at(&file, offset + position as u64, size)?;
+                let (kind, base) =
+                    self.read_packed(pack, offset.checked_sub(distance)?, depth + 1)?;
+                Some((kind, apply_delta(&base, &delta)?))
+            }
+            7 => {
+                // reference delta: the base is named by its id
+                let base_oid: Oid = header.get(position..position + 20)?.try_into().ok()?;
+                let delta = inflate_at(&file, offset + position as u64 + 20, size)?;
+                let (kind, base) = self.read_with_depth(&base_oid, depth + 1)?;
+                Some((kind, apply_delta(&base, &delta)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Pack {
+    /// Offset of the object in the pack, found through the version 2 `.idx` file.
+    fn find(&self, oid: &Oid) -> Option<u64> {
+        let index = &self.index;
+        let fanout = |i: usize| -> Option<usize> { Some(read_u32(index, 8 + i * 4)? as usize) };
+
+        let total = fanout(255)?;
+        let first = oid[0] as usize;
+        let mut low = if first == 0 { 0 } else { fanout(first - 1)? };
+        let mut high = fanout(first)?;
+
+        let ids_start = 8 + 256 * 4;
+        while low < high {
+            let middle = (low + high) / 2;
+            let start = ids_start + middle * 20;
+            let candidate = index.get(start..start + 20)?;
+
+            match candidate.cmp(oid.as_slice()) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => {
+                    let offsets_start = ids_start + total * 24;
+                    let offset = read_u32(index, offsets_start + middle * 4)?;
+                    if offset & 0x8000_0000 == 0 {
+                        return Some(offset as u64);
+                    }
+
+                    // large offsets live in a separate 8-byte table
+                    let large_start =
+                        offsets_start + total * 4 + (offset & 0x7fff_ffff) as usize * 8;
+                    let bytes: [u8; 8] =
+                        index.get(large_start..large_start + 8)?.try_into().ok()?;
+                    return Some(u64::from_be_bytes(bytes));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Inflates a zlib stream starting at `offset`, reading more of the file until it is complete.
+fn inflate_at(file: &File, offset: u64, size: usize) -> Option<Vec<u8>> {
+    let file_len = file.metadata().ok()?.len();
+    let mut chunk = size + 1024;
+
+    loop {
+        let available = file_len.saturating_sub(offset) as usize;
+        let mut buffer = vec![0u8; chunk.min(available)];
+        let read = file.read_at(&mut buffer, offset).ok()?;
+        buffer.truncate(read);
+
+        match inflate::decompress_to_vec_zlib_with_limit(&buffer, size + 1) {
+            Ok(data) => return Some(data),
+            Err(_) if chunk < available => chunk *= 2,
+            Err(_) => return None,
+        }
+    }
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut position = 0;
+    let read_size = |position: &mut usize| -> Option<usize> {
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(*position)?;
+            *position += 1;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(size);
+            }
+        }
+    };
+
+    let base_size = read_size(&mut position)?;
+    let result_size = read_size(&mut position)?;
+    if base_size != base.len() {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(result_size);
+    while position < delta.len() {
+        let instruction = delta[position];
+        position += 1;
+
+        if instruction & 0x80 != 0 {
+            // copy from the base: which offset/size bytes are present is given by the bits
+            let mut copy_offset = 0usize;
+            let mut copy_size = 0usize;
+            for bit in 0..4 {
+                if instruction & (1 << bit) != 0 {
+                    copy_offset |= (*delta.get(position)? as usize) << (bit * 8);
+                    position += 1;
+                }
+            }
+            for bit in 0..3 {
+                if instruction & (1 << (bit + 4)) != 0 {
+                    copy_size |= (*delta.get(position)? as usize) << (bit * 8);
+                    position += 1;
+                }
+            }
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+            result.extend_from_slice(base.get(copy_offset..copy_offset + copy_size)?);
+        } else if instruction != 0 {
+            let length = instruction as usize;
+            result.extend_from_slice(delta.get(position..position + length)?);
+            position += length;
+        } else {
+            return None;
+        }
+    }
+
+    (result.len() == result_size).then_some(result)
+}
+
+pub fn parse_commit(data: &[u8]) -> Option<Commit> {
+    let text = String::from_utf8_lossy(data);
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut time = 0;
+
+    for line in text.lines() {
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("tree ") {
+            tree = parse_hex(value);
+        } else if let Some(value) = line.strip_prefix("parent ") {
+            parents.extend(parse_hex(value));
+        } else if let Some(value) = line.strip_prefix("committer ") {
+            // "name <email> <epoch> <timezone>"
+            time = value
+                .rsplit(' ')
+                .nth(1)
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default();
+        }
+    }
+
+    Some(Commit {
+        tree: tree?,
+        parents,
+        time,
+    })
+}
+
+pub fn parse_tree(data: &[u8]) -> Vec<TreeEntry> {
+    let mut entries = Vec::new();
+    let mut position = 0;
+
+    // "<octal mode> <name>\0<20 byte id>" repeated
+    while position < data.len() {
+        let Some(space) = data[position..].iter().position(|&b| b == b' ') else {
+            break;
+        };
+        let Some(nul) = data[position + space..].iter().position(|&b| b == 0) else {
+            break;
+        };
+        let name_end = position + space + nul;
+        let Some(oid) = data.get(name_end + 1..name_end + 21) else {
+            break;
+        };
+
+        let mode = std::str::from_utf8(&data[position..position + space])
+            .ok()
+            .and_then(|m| u32::from_str_radix(m, 8).ok())
+            .unwrap_or_default();
+        entries.push(TreeEntry {
+            mode,
+            name: String::from_utf8_lossy(&data[position + space + 1..name_end]).to_string(),
+            oid: oid.try_into().unwrap_or([0; 20]),
+        });
+
+        position = name_end + 21;
+    }
+
+    entries
+}
+
+pub fn parse_hex(hex: &str) -> Option<Oid> {
+    let hex = hex.trim();
+    if hex.len() < 40 {
+        return None;
+    }
+
+    let mut oid = [0u8; 20];
+    for (i, byte) in oid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(oid)
+}
+
+pub fn to_hex(oid: &Oid) -> String {
+    oid.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Id git gives to a blob with this content.
+pub fn hash_blob(content: &[u8]) -> Oid {
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    hasher.digest().bytes()
+}
+
+pub fn read_u32(data: &[u8], position: usize) -> Option<u32> {
+    let bytes: [u8; 4] = data.get(position..position + 4)?.try_into().ok()?;
+    Some(u32::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_copy_and_insert_instructions() {
+        let base = b"hello, world";
+        let delta = [
+            12, 13, // base and result sizes
+            0x91, 7, 5, // copy 5 bytes from offset 7: "world"
+            2, b',', b' ', // insert ", "
+            0x90, 6, // copy 6 bytes from offset 0: "hello,"
+        ];
+
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"world, hello,");
+    }
+
+    #[test]
+    fn copy_size_zero_means_64_kib() {
+        let base = vec![b'x'; 0x10000];
+        // the sizes are 0x10000 as a little-endian varint
+        let delta = [0x80, 0x80, 0x04, 0x80, 0x80, 0x04, 0x80];
+
+        assert_eq!(apply_delta(&base, &delta).unwrap(), base);
+    }
+
+    #[test]
+    fn rejects_malformed_deltas() {
+        let base = b"hello";
+        // wrong base size
+        assert!(apply_delta(base, &[4, 1, 1, b'x']).is_none());
+        // wrong result size
+        assert!(apply_delta(base, &[5, 3, 1, b'x']).is_none());
+        // copy past the end of the base
+        assert!(apply_delta(base, &[5, 4, 0x91, 3, 4]).is_none());
+        // truncated insert
+        assert!(apply_delta(base, &[5, 3, 3, b'x']).is_none());
+        // reserved instruction
+        assert!(apply_delta(base, &[5, 0, 0]).is_none());
+    }
+}
@@ -1,16 +1,29 @@
 mod commands;
+mod git;
 mod os;
 mod parser;
 mod shell;
 
 use std::collections::{HashMap, HashSet};
 use std::env;
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
-use std::process::ChildStdout;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ChildStdout, ExitStatus};
+use std::time::{Duration, Instant};
 
 use crate::{
+    git::GitCache,
     os::OSInstance,
-    shell::{CommandInput, CommandOutput, output},
+    shell::{
+        CommandInput, CommandOutput,
+        command_hash::CommandHash,
+        completion::CompletionRegistry,
+        frecency,
+        history::History,
+        history_expansion, output,
+        prompt::{self, PromptContext},
+    },
 };
 
 enum OutputProcessor {
@@ -22,9 +35,17 @@ enum OutputProcessor {
 fn main() {
     let mut output_processor = OutputProcessor::Console;
     let mut current_dir: PathBuf = env::current_dir().unwrap_or_default();
-    let mut command_history: Vec<String> = commands::load_history();
+    let mut command_history = History::load();
+    let mut completions = CompletionRegistry::new();
+    let mut last_status: i32 = 0;
+    let mut last_duration: Option<Duration> = None;
+    let mut git_cache = GitCache::default();
+    let mut dir_stack: Vec<PathBuf> = Vec::new();
+    let mut command_hash = CommandHash::default();
 
-    let os_instance = OSInstance::new();
+    let mut os_instance = OSInstance::new();
+    // SAFETY: the shell is single-threaded, nothing else reads the environment concurrently
+    unsafe { env::set_var("PWD", &current_dir) };
 
     let mut commands: HashMap<&str, fn(CommandInput) -> CommandOutput> = HashMap::new();
     commands.insert("echo", commands::echo);
@@ -33,7 +54,16 @@ fn main() {
     commands.insert("cd", commands::cd);
     commands.insert("dir", commands::ls);
     commands.insert("type", commands::type_fn);
+    commands.insert("which", commands::which);
+    commands.insert("command", commands::command);
+    commands.insert("hash", commands::hash);
     commands.insert("history", commands::history);
+    commands.insert("complete", commands::complete);
+    commands.insert("pushd", commands::pushd);
+    commands.insert("popd", commands::popd);
+    commands.insert("dirs", commands::dirs);
+    commands.insert("z", commands::z);
+    commands.insert("zi", commands::zi);
 
     let mut know_commands: HashSet<String> = HashSet::new();
 
@@ -47,86 +77,209 @@ fn main() {
     }
 
     loop {
-        let user_input = shell::input::retrieve_user_input(&know_commands, &command_history);
-        let command_input: Vec<&str> = user_input.trim().split('|').collect();
-        let last_command_position = &command_input.len() - 1;
-        let mut previous_result: Option<String> = None;
+        // pick up commands installed since the last prompt, or a new PATH
+        if os_instance.refresh() {
+            know_commands = shell_commands
+                .iter()
+                .cloned()
+                .chain(os_instance.get_know_commands())
+                .collect();
+        }
+        command_hash.sync_path();
 
-        let mut program_run_children = Vec::new();
-        let mut previous_stdout: Option<ChildStdout> = None;
+        if shell::option_enabled("share_history") {
+            command_history.merge_shared();
+        }
 
-        for (position, command) in command_input.into_iter().enumerate() {
-            let words = parser::parse_input(command);
+        let prompt = prompt::build(&mut PromptContext {
+            current_dir: &current_dir,
+            last_status,
+            last_duration,
+            history_number: command_history.next_number(),
+            git_cache: &mut git_cache,
+        });
+        let user_input = shell::input::retrieve_user_input(
+            &prompt,
+            &know_commands,
+            command_history.entries(),
+            &completions,
+            &current_dir,
+        );
 
-            match output::define_output_processor(command, &current_dir) {
-                Ok(processor) => output_processor = processor,
-                Err(message) => {
-                    println!("{}", message);
+        let expansion = history_expansion::expand(
+            &user_input,
+            command_history.entries(),
+            command_history.first_number(),
+        );
+        let user_input = match expansion {
+            Ok(expansion) => {
+                if expansion.expanded {
+                    println!("{}", expansion.line);
+                }
+                if expansion.print_only {
+                    command_history.record(&expansion.line);
                     continue;
                 }
-            };
-
-            if let Some(command_name) = words.first() {
-                command_history.push(user_input.clone());
-                let action_requested = commands.get(&command_name.as_str());
-
-                let input = CommandInput {
-                    command_name: command_name.as_str(),
-                    command_arguments: &words[1..],
-                    current_dir: &current_dir,
-                    os: &os_instance,
-                    command_history: &command_history,
-                    shell_commands: &shell_commands,
-                    std_input: previous_result.clone(),
-                };
+                expansion.line
+            }
+            Err(message) => {
+                println!("{message}");
+                last_status = 1;
+                continue;
+            }
+        };
+
+        let started_at = Instant::now();
+        for line in parser::split_lines(&user_input) {
+            command_history.record(&line);
+
+            let command_input: Vec<&str> = parser::split_pipeline(line.trim());
+            let last_command_position = &command_input.len() - 1;
+            let mut previous_result: Option<String> = None;
 
-                if let Some(action) = action_requested {
-                    let result = action(input);
+            let mut program_run_children = Vec::new();
+            let mut previous_stdout: Option<ChildStdout> = None;
 
-                    // process results
-                    if let Some(path) = result.updated_dir {
-                        current_dir = path;
+            for (position, command) in command_input.into_iter().enumerate() {
+                let mut words = parser::parse_input(command);
+
+                // `command name args` runs `name`; only `-v`/`-V` reach the builtin
+                if words.first().is_some_and(|word| word == "command")
+                    && words.get(1).is_some_and(|word| !word.starts_with('-'))
+                {
+                    words.remove(0);
+                }
+
+                // `autocd`: a bare directory name that isn't a command runs as `cd name`
+                if shell::option_enabled("autocd")
+                    && let [name] = words.as_slice()
+                    && !commands.contains_key(name.as_str())
+                    && os_instance.find_executable(name, &current_dir).is_none()
+                    && parser::parse_path(name, &current_dir).is_ok_and(|path| path.is_dir())
+                {
+                    words.insert(0, String::from("cd"));
+                }
+
+                match output::define_output_processor(command, &current_dir) {
+                    Ok(processor) => output_processor = processor,
+                    Err(message) => {
+                        println!("{}", message);
+                        last_status = 1;
+                        continue;
                     }
+                };
+
+                if let Some(command_name) = words.first() {
+                    let action_requested = commands.get(&command_name.as_str());
 
-                    if let Some(history) = result.command_history {
-                        for item in history {
-                            command_history.push(item);
+                    let input = CommandInput {
+                        command_name: command_name.as_str(),
+                        command_arguments: &words[1..],
+                        current_dir: &current_dir,
+                        os: &os_instance,
+                        command_history: &command_history,
+                        shell_commands: &shell_commands,
+                        completions: &completions,
+                        dir_stack: &dir_stack,
+                        command_hash: &command_hash,
+                        std_input: previous_result.clone(),
+                        terminal_output: position == last_command_position
+                            && matches!(output_processor, OutputProcessor::Console)
+                            && io::stdout().is_terminal(),
+                    };
+
+                    if let Some(action) = action_requested {
+                        let result = action(input);
+                        last_status = result
+                            .status
+                            .unwrap_or(if result.std_error.is_some() { 1 } else { 0 });
+
+                        // process results
+                        if let Some(path) = result.updated_dir {
+                            // SAFETY: the shell is single-threaded, children get these on spawn
+                            unsafe {
+                                env::set_var("OLDPWD", &current_dir);
+                                env::set_var("PWD", &path);
+                            }
+                            frecency::record(&path);
+                            current_dir = path;
                         }
-                    }
 
-                    output::process_output(
-                        &output_processor,
-                        result.std_output.clone(),
-                        result.std_error,
-                        position == last_command_position,
-                    );
-                    previous_result = result.std_output;
+                        if let Some(stack) = result.dir_stack {
+                            dir_stack = stack;
+                        }
+
+                        if let Some(hash) = result.command_hash {
+                            command_hash = hash;
+                        }
+
+                        if let Some(history) = result.command_history {
+                            command_history = history;
+                        }
+
+                        if let Some(registry) = result.completions {
+                            completions = registry;
+                        }
+
+                        output::process_output(
+                            &output_processor,
+                            result.std_output.clone(),
+                            result.std_error,
+                            position == last_command_position,
+                        );
+                        previous_result = result.std_output;
+                    } else {
+                        let is_last = position == last_command_position;
+                        let has_redirect = !matches!(output_processor, OutputProcessor::Console);
+                        match commands::run_program(input, &mut previous_stdout, is_last, has_redirect)
+                        {
+                            Ok((result, hashable)) => {
+                                if let Some(path) = hashable {
+                                    command_hash.hit(command_name, &path);
+                                }
+                                program_run_children.push((result, is_last, has_redirect))
+                            }
+                            Err(error) => {
+                                println!("{error}");
+                                last_status = 127;
+                            }
+                        }
+                    };
+                }
+            }
+
+            // Wait for all children
+            for (mut child, is_last, has_redirect) in program_run_children {
+                let status = if is_last && has_redirect {
+                    let result = child.wait_with_output().expect("failed to wait");
+                    let std_output = parse_child_output(result.stdout);
+                    let std_error = parse_child_output(result.stderr);
+                    output::process_output(&output_processor, std_output, std_error, true);
+                    result.status
                 } else {
-                    let is_last = position == last_command_position;
-                    let has_redirect = !matches!(output_processor, OutputProcessor::Console);
-                    match commands::run_program(input, &mut previous_stdout, is_last, has_redirect)
-                    {
-                        Ok(result) => program_run_children.push((result, is_last && has_redirect)),
-                        Err(error) => println!("{error}"),
-                    }
+                    child.wait().expect("failed to wait")
                 };
+
+                if is_last {
+                    last_status = exit_code(status);
+                }
             }
         }
 
-        // Wait for all children
-        for (mut child, capture_output) in program_run_children {
-            if capture_output {
-                let result = child.wait_with_output().expect("failed to wait");
-                let std_output = parse_child_output(result.stdout);
-                let std_error = parse_child_output(result.stderr);
-                output::process_output(&output_processor, std_output, std_error, true);
-            } else {
-                child.wait().expect("failed to wait");
-            }
+        if !user_input.trim().is_empty() {
+            last_duration = Some(started_at.elapsed());
         }
     }
 }
 
+/// Exit code as the shell reports it: 128 + signal number when the child was killed.
+fn exit_code(status: ExitStatus) -> i32 {
+    match status.code() {
+        Some(code) => code,
+        None => 128 + status.signal().unwrap_or_default(),
+    }
+}
+
 fn parse_child_output(raw: Vec<u8>) -> Option<String> {
     match String::from_utf8(raw) {
         Ok(s) if s.is_empty() => None,
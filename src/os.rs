@@ -1,22 +1,61 @@
 use is_executable::IsExecutable;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env,
-    ffi::OsString,
-    fs,
+    ffi::{CString, OsString},
+    fs, io,
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 pub struct OSInstance {
     path_commands: HashMap<OsString, PathBuf>,
+    /// `PATH` and the modification time of each of its directories at the last scan.
+    scanned_path: Option<OsString>,
+    scanned_dirs: Vec<(PathBuf, Option<SystemTime>)>,
 }
 
 impl OSInstance {
     pub fn new() -> OSInstance {
-        OSInstance {
-            path_commands: load_path_commands(),
+        let mut instance = OSInstance {
+            path_commands: HashMap::new(),
+            scanned_path: None,
+            scanned_dirs: Vec::new(),
+        };
+        instance.scan();
+        instance
+    }
+
+    /// Scans `PATH` again when it was changed, or when one of its directories was modified since
+    /// the last scan (a command installed or removed). Returns whether it did.
+    pub fn refresh(&mut self) -> bool {
+        let unchanged = env::var_os("PATH") == self.scanned_path
+            && self
+                .scanned_dirs
+                .iter()
+                .all(|(dir, modified)| modification_time(dir) == *modified);
+        if unchanged {
+            return false;
         }
+
+        self.scan();
+        true
+    }
+
+    fn scan(&mut self) {
+        self.scanned_path = env::var_os("PATH");
+        self.scanned_dirs = self
+            .scanned_path
+            .iter()
+            .flat_map(env::split_paths)
+            .map(|dir| {
+                let modified = modification_time(&dir);
+                (dir, modified)
+            })
+            .collect();
+        self.path_commands = load_path_commands(&self.scanned_dirs);
     }
 
     pub fn find_executable(&self, name: &str, current_dir: &Path) -> Option<PathBuf> {
@@ -26,9 +65,29 @@ impl OSInstance {
         }
 
         // search path
-        self.path_commands
-            .get(&OsString::from(name))
-            .cloned()
+        self.find_in_path(name)
+    }
+
+    /// The first executable called `name` in the `PATH` directories.
+    pub fn find_in_path(&self, name: &str) -> Option<PathBuf> {
+        self.path_commands.get(&OsString::from(name)).cloned()
+    }
+
+    /// Every executable `name` resolves to, in lookup order: the current folder, then each
+    /// `PATH` directory.
+    pub fn find_all_executables(&self, name: &str, current_dir: &Path) -> Vec<PathBuf> {
+        let mut executables: Vec<PathBuf> = find_executable_folder(name, current_dir).into_iter().collect();
+
+        if let Some(path) = env::var_os("PATH") {
+            for path_item in env::split_paths(&path) {
+                let file_path = path_item.join(name);
+                if file_path.is_file() && file_path.is_executable() && !executables.contains(&file_path) {
+                    executables.push(file_path);
+                }
+            }
+        }
+
+        executables
     }
 
     pub(crate) fn get_know_commands(&self) -> Vec<String> {
@@ -39,28 +98,151 @@ impl OSInstance {
     }
 }
 
-fn load_path_commands() -> HashMap<OsString, PathBuf> {
+/// Users and their home directories, read from `/etc/passwd`.
+pub fn user_home_dirs() -> Vec<(String, PathBuf)> {
+    let Ok(content) = fs::read_to_string("/etc/passwd") else {
+        return Vec::new();
+    };
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            match (fields.first(), fields.get(5)) {
+                (Some(name), Some(home)) if !name.is_empty() => {
+                    Some((name.to_string(), PathBuf::from(home)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and never fails
+    unsafe { libc::getuid() }
+}
+
+/// Name of the user with the given uid, read from `/etc/passwd`.
+pub fn user_name(uid: u32) -> Option<String> {
+    user_names().remove(&uid)
+}
+
+/// User names by uid, read from `/etc/passwd`.
+pub fn user_names() -> HashMap<u32, String> {
+    names_by_id("/etc/passwd")
+}
+
+/// Group names by gid, read from `/etc/group`.
+pub fn group_names() -> HashMap<u32, String> {
+    names_by_id("/etc/group")
+}
+
+/// The `name:password:id:...` lines of `/etc/passwd` or `/etc/group`, by id. The first line
+/// wins when several share an id.
+fn names_by_id(path: &str) -> HashMap<u32, String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    let mut names = HashMap::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if let (Some(name), Some(Ok(id))) = (fields.first(), fields.get(2).map(|id| id.parse()))
+        {
+            names.entry(id).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+pub fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+
+    // SAFETY: the buffer is valid for its whole length, which is passed along
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return String::new();
+    }
+
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).to_string()
+}
+
+/// Checks that `dir` is a directory the shell can change into: it exists, is a directory (after
+/// following symlinks) and has search permission.
+pub fn check_directory(dir: &Path) -> io::Result<()> {
+    if !fs::metadata(dir)?.is_dir() {
+        return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+    }
+
+    let c_path = CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: the path is a valid NUL-terminated string that outlives the call
+    if unsafe { libc::access(c_path.as_ptr(), libc::X_OK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// An I/O error as coreutils word it, without the `(os error N)` suffix.
+pub fn describe_error(error: &io::Error) -> String {
+    let text = error.to_string();
+    match text.find(" (os error") {
+        Some(end) => text[..end].to_string(),
+        None => text,
+    }
+}
+
+/// Current time as a Unix timestamp.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Formats a Unix timestamp in local time with a `strftime` format.
+pub fn format_time(timestamp: i64, format: &str) -> String {
+    let Ok(c_format) = CString::new(format) else {
+        return String::new();
+    };
+    let time = timestamp as libc::time_t;
+    // SAFETY: `tm` is a plain C struct of integers (and a nullable pointer on some platforms),
+    // for which all zero bytes is a valid value
+    let mut parts: libc::tm = unsafe { std::mem::zeroed() };
+    let mut buffer = [0u8; 256];
+
+    // SAFETY: all pointers reference live locals and the buffer length is passed along
+    let written = unsafe {
+        if libc::localtime_r(&time, &mut parts).is_null() {
+            return String::new();
+        }
+        libc::strftime(buffer.as_mut_ptr().cast(), buffer.len(), c_format.as_ptr(), &parts)
+    };
+
+    String::from_utf8_lossy(&buffer[..written]).to_string()
+}
+
+fn load_path_commands(dirs: &[(PathBuf, Option<SystemTime>)]) -> HashMap<OsString, PathBuf> {
     let mut commands: HashMap<OsString, PathBuf> = HashMap::new();
 
-    if let Some(path) = std::env::var_os("PATH") {
-        for path_item in env::split_paths(&path) {
-            let Ok(read_dir_value) = fs::read_dir(path_item) else {
+    for (path_item, _) in dirs {
+        let Ok(read_dir_value) = fs::read_dir(path_item) else {
+            continue;
+        };
+
+        for entry in read_dir_value {
+            let Ok(entry_result) = entry else {
                 continue;
             };
 
-            for entry in read_dir_value {
-                let Ok(entry_result) = entry else {
-                    continue;
-                };
+            let file_path = entry_result.path();
 
-                let file_path = entry_result.path();
-
-                if file_path.is_executable()
-                    && let Some(name) = file_path.file_name()
-                    && !commands.contains_key(name)
-                {
-                    commands.insert(name.to_os_string(), file_path);
-                }
+            if file_path.is_executable()
+                && let Some(name) = file_path.file_name()
+                && !commands.contains_key(name)
+            {
+                commands.insert(name.to_os_string(), file_path);
             }
         }
     }
@@ -68,6 +250,23 @@ fn load_path_commands() -> HashMap<OsString, PathBuf> {
     commands
 }
 
+fn modification_time(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Names of the executable files in `dir`.
+pub fn executable_names(dir: &Path) -> HashSet<String> {
+    let Ok(read_dir_value) = fs::read_dir(dir) else {
+        return HashSet::new();
+    };
+
+    read_dir_value
+        .flatten()
+        .filter(|entry| entry.path().is_executable())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect()
+}
+
 fn find_executable_folder(name: &str, path_item: &Path) -> Option<PathBuf> {
     let Ok(read_dir_value) = fs::read_dir(path_item) else {
         return None;
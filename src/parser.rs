@@ -1,84 +1,273 @@
 use std::{
     env,
+    iter::Peekable,
+    ops::Range,
     path::{Component, Path, PathBuf},
+    str::CharIndices,
 };
 
-enum Parser {
-    SingleQuote,
-    DoubleQuote,
-    Escape,
-    EscapeInDoubleQuote,
-    None,
+#[derive(Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Word,
+    /// `|`, `||`, `&`, `&&`, `;` or a newline.
+    Operator,
+    /// `>`, `>>` or `<`, with an optional file descriptor number before it (`2>`, `1>>`).
+    Redirect,
+    /// From a `#` starting a word to the end of the line.
+    Comment,
 }
 
-pub fn parse_input(argument: &str) -> Vec<String> {
-    let mut arguments: Vec<String> = Vec::new();
-    let mut current_argument = String::new();
-
-    let mut current_parser = Parser::None;
-
-    for character in argument.chars() {
-        match current_parser {
-            Parser::None => {
-                if character == '\'' {
-                    current_parser = Parser::SingleQuote;
-                } else if character == '"' {
-                    current_parser = Parser::DoubleQuote;
-                } else if character == '\\' {
-                    current_parser = Parser::Escape;
-                } else if character == ' ' {
-                    if !current_argument.is_empty() {
-                        if matches!(current_argument.as_str(), ">" | "1>" | "2>" | ">>" | "1>>" | "2>>") {
-                            current_argument.clear();
-                            break; // redirect stout argument
-                        }
-                        arguments.push(current_argument.clone());
-                        current_argument.clear();
-                    }
-                } else {
-                    current_argument.push(character);
-                }
+/// Pieces of a word that are worth telling apart, e.g. for highlighting.
+#[derive(Clone, Copy, PartialEq)]
+pub enum WordPart {
+    Quoted,
+    /// A quote that is never closed, or a backslash at the very end.
+    Unterminated,
+    Variable,
+}
+
+pub struct Token {
+    pub kind: TokenKind,
+    /// Bytes of the input the token comes from, quotes and escapes included.
+    pub span: Range<usize>,
+    /// The word after quote and escape removal, or the text of any other token.
+    pub value: String,
+    /// Quoted strings and variables inside a word, as byte ranges of the input.
+    pub parts: Vec<(Range<usize>, WordPart)>,
+}
+
+/// Splits a command line into words, operators, redirections and comments, keeping where each
+/// one comes from. Everything that interprets the input (argument parsing, pipelines, redirects,
+/// highlighting) goes through here, so they always agree.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut word: Option<Token> = None;
+    let mut characters = input.char_indices().peekable();
+
+    while let Some(&(start, character)) = characters.peek() {
+        match character {
+            ' ' | '\t' => {
+                characters.next();
+                tokens.extend(word.take());
+            }
+            // line continuation
+            '\\' if input[start + 1..].starts_with('\n') => {
+                characters.next();
+                characters.next();
             }
-            Parser::DoubleQuote => {
-                if character == '"' {
-                    current_parser = Parser::None;
-                } else if character == '\\' {
-                    current_parser = Parser::EscapeInDoubleQuote;
+            '#' if word.is_none() => {
+                let end = input[start..].find('\n').map_or(input.len(), |p| start + p);
+                while characters.next_if(|&(i, _)| i < end).is_some() {}
+                tokens.push(operator_token(TokenKind::Comment, input, start..end));
+            }
+            '|' | '&' | ';' | '\n' => {
+                tokens.extend(word.take());
+                characters.next();
+                // `||` and `&&`
+                if character != '\n'
+                    && characters.next_if(|&(_, c)| c == character && c != ';').is_some()
+                {
+                    tokens.push(operator_token(TokenKind::Operator, input, start..start + 2));
                 } else {
-                    current_argument.push(character);
+                    tokens.push(operator_token(TokenKind::Operator, input, start..start + 1));
                 }
             }
-            Parser::SingleQuote => {
-                if character == '\'' {
-                    current_parser = Parser::None;
-                } else {
-                    current_argument.push(character);
+            '>' | '<' => {
+                // a word made only of digits right before is the file descriptor
+                let redirect_start = match word.take() {
+                    Some(digits)
+                        if digits.span.len() == digits.value.len()
+                            && digits.value.chars().all(|c| c.is_ascii_digit()) =>
+                    {
+                        digits.span.start
+                    }
+                    other => {
+                        tokens.extend(other);
+                        start
+                    }
+                };
+
+                characters.next();
+                let mut end = start + 1;
+                if character == '>' && characters.next_if(|&(_, c)| c == '>').is_some() {
+                    end += 1;
                 }
+                tokens.push(operator_token(TokenKind::Redirect, input, redirect_start..end));
             }
-            Parser::Escape => {
-                current_argument.push(character);
-                current_parser = Parser::None;
+            _ => {
+                let token = word.get_or_insert_with(|| Token {
+                    kind: TokenKind::Word,
+                    span: start..start,
+                    value: String::new(),
+                    parts: Vec::new(),
+                });
+                read_word_part(input, &mut characters, token);
+                token.span.end = characters.peek().map_or(input.len(), |&(i, _)| i);
             }
-            Parser::EscapeInDoubleQuote => {
-                if matches!(character, '"' | '\\') {
-                    current_argument.push(character);
-                } else {
-                    current_argument.push('\\');
-                    current_argument.push(character);
-                }
+        }
+    }
+
+    tokens.extend(word);
+    tokens
+}
 
-                current_parser = Parser::DoubleQuote;
+fn operator_token(kind: TokenKind, input: &str, span: Range<usize>) -> Token {
+    Token {
+        kind,
+        value: input[span.clone()].to_string(),
+        span,
+        parts: Vec::new(),
+    }
+}
+
+/// Reads one piece of a word: a quoted string, an escaped character, a variable or a plain character.
+fn read_word_part(input: &str, characters: &mut Peekable<CharIndices>, token: &mut Token) {
+    let Some((start, character)) = characters.next() else {
+        return;
+    };
+    let position =
+        |characters: &mut Peekable<CharIndices>| characters.peek().map_or(input.len(), |&(i, _)| i);
+
+    match character {
+        '\'' => {
+            let mut closed = false;
+            for (_, c) in characters.by_ref() {
+                if c == '\'' {
+                    closed = true;
+                    break;
+                }
+                token.value.push(c);
+            }
+            let kind = if closed { WordPart::Quoted } else { WordPart::Unterminated };
+            token.parts.push((start..position(characters), kind));
+        }
+        '"' => {
+            let mut closed = false;
+            while let Some((index, c)) = characters.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match characters.next() {
+                        // line continuation
+                        Some((_, '\n')) => {}
+                        Some((_, escaped @ ('"' | '\\'))) => token.value.push(escaped),
+                        Some((_, other)) => {
+                            token.value.push('\\');
+                            token.value.push(other);
+                        }
+                        None => token.value.push('\\'),
+                    },
+                    '$' => {
+                        let end = variable_end(input, index);
+                        token.value.push_str(&input[index..end]);
+                        while characters.next_if(|&(i, _)| i < end).is_some() {}
+                        if end > index + 1 {
+                            token.parts.push((index..end, WordPart::Variable));
+                        }
+                    }
+                    other => token.value.push(other),
+                }
+            }
+            let kind = if closed { WordPart::Quoted } else { WordPart::Unterminated };
+            // the quoted string goes first so variables inside it are drawn over it
+            let first_variable = token
+                .parts
+                .iter()
+                .rposition(|(range, _)| range.start < start)
+                .map_or(0, |p| p + 1);
+            token.parts.insert(first_variable, (start..position(characters), kind));
+        }
+        '\\' => match characters.next() {
+            Some((_, escaped)) => token.value.push(escaped),
+            None => token.parts.push((start..input.len(), WordPart::Unterminated)),
+        },
+        '$' => {
+            let end = variable_end(input, start);
+            token.value.push_str(&input[start..end]);
+            while characters.next_if(|&(i, _)| i < end).is_some() {}
+            if end > start + 1 {
+                token.parts.push((start..end, WordPart::Variable));
             }
         }
+        other => token.value.push(other),
     }
+}
+
+/// End of a `$NAME`, `${...}` or special (`$?`, `$1`, ...) variable reference starting at `start`.
+/// Just past the `$` when it doesn't start one.
+fn variable_end(input: &str, start: usize) -> usize {
+    let rest = &input[start + 1..];
+
+    match rest.chars().next() {
+        Some('{') => rest.find('}').map_or(input.len(), |p| start + 1 + p + 1),
+        Some('?' | '$' | '!' | '#' | '@' | '*' | '-' | '0'..='9') => start + 2,
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            start + 1 + length
+        }
+        _ => start + 1,
+    }
+}
+
+pub fn parse_input(argument: &str) -> Vec<String> {
+    let mut arguments: Vec<String> = Vec::new();
 
-    if !current_argument.is_empty() {
-        arguments.push(current_argument);
+    for token in tokenize(argument) {
+        match token.kind {
+            TokenKind::Word => arguments.push(token.value),
+            // the redirect target and anything after it aren't arguments
+            TokenKind::Redirect => break,
+            TokenKind::Comment => {}
+            // pipes are split before; the other control operators aren't supported yet
+            TokenKind::Operator => arguments.push(token.value),
+        }
     }
 
     arguments
 }
 
+/// Splits an input buffer into the command lines it holds. A backslash before a newline joins
+/// the two lines, and newlines inside quotes stay part of the argument. Blank lines are dropped.
+pub fn split_lines(input: &str) -> Vec<String> {
+    split_at_operator(input, "\n")
+        .into_iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// The commands of a pipeline, split at each `|` operator.
+pub fn split_pipeline(line: &str) -> Vec<&str> {
+    split_at_operator(line, "|")
+}
+
+fn split_at_operator<'a>(input: &'a str, operator: &str) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+
+    for token in tokenize(input) {
+        if token.kind == TokenKind::Operator && token.value == operator {
+            pieces.push(&input[start..token.span.start]);
+            start = token.span.end;
+        }
+    }
+
+    pieces.push(&input[start..]);
+    pieces
+}
+
+/// Whether the input stops inside quotes or right after a backslash, so Enter should open
+/// a continuation line instead of running it.
+pub fn is_incomplete(input: &str) -> bool {
+    tokenize(input)
+        .last()
+        .is_some_and(|token| token.parts.iter().any(|(_, part)| *part == WordPart::Unterminated))
+}
+
 pub fn parse_path(path: &str, current_dir: &Path) -> Result<PathBuf, String> {
     let path = if path.starts_with("~") {
         let Some(home_dir) = env::var("HOME").ok().map(PathBuf::from) else {
@@ -110,3 +299,121 @@ pub fn parse_path(path: &str, current_dir: &Path) -> Result<PathBuf, String> {
 
     Ok(target_dir)
 }
+
+/// Shell-style pattern matching: `*` matches any run of characters, `?` a single one, and
+/// `[...]` a set (`[!...]` or `[^...]` to negate, with `a-z` ranges). `\` escapes the next character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let mut p = 0;
+    let mut t = 0;
+    // where to resume after the last `*`: (pattern position after it, text position it matched up to)
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match match_set(&pattern, p, text[t]) {
+                Some((true, next)) => Some(next),
+                Some((false, _)) => None,
+                // no closing bracket: match '[' literally
+                None => (text[t] == '[').then_some(p + 1),
+            },
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(p + 2),
+            Some(&c) => (c == text[t]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            }
+            (None, Some((star_next, star_text))) => {
+                p = star_next;
+                t = star_text + 1;
+                backtrack = Some((star_next, star_text + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `character` against the set starting at `pattern[start] == '['`.
+/// Returns whether it matched and the position after the closing `]`.
+fn match_set(pattern: &[char], start: usize, character: char) -> Option<(bool, usize)> {
+    let mut position = start + 1;
+    let negated = matches!(pattern.get(position), Some('!' | '^'));
+    if negated {
+        position += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+
+    while let Some(&c) = pattern.get(position) {
+        if c == ']' && !first {
+            return Some((matched != negated, position + 1));
+        }
+        first = false;
+
+        if pattern.get(position + 1) == Some(&'-')
+            && let Some(&end) = pattern.get(position + 2)
+            && end != ']'
+        {
+            matched |= (c..=end).contains(&character);
+            position += 3;
+        } else {
+            matched |= c == character;
+            position += 1;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(glob_match("*.rs", ".rs"));
+        assert!(!glob_match("*.rs", "main.rsx"));
+        assert!(glob_match("a*b*c", "aXXbYYbc"));
+        assert!(glob_match("ma?n.rs", "main.rs"));
+        assert!(!glob_match("ma?n.rs", "man.rs"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("**", "anything"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn glob_sets() {
+        assert!(glob_match("file[0-9].txt", "file7.txt"));
+        assert!(!glob_match("file[0-9].txt", "fileA.txt"));
+        assert!(glob_match("[!a-c]x", "dx"));
+        assert!(!glob_match("[^a-c]x", "bx"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[a-]", "-"));
+        // an unclosed set is a literal `[`
+        assert!(glob_match("[abc", "[abc"));
+    }
+
+    #[test]
+    fn glob_escapes_and_unicode() {
+        assert!(glob_match("\\*", "*"));
+        assert!(!glob_match("\\*", "x"));
+        assert!(glob_match("caf?", "café"));
+        assert!(glob_match("*é", "café"));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use is_executable::IsExecutable;
+
+/// A remembered command location, and how many times it was run from there.
+#[derive(Clone)]
+pub struct HashedCommand {
+    pub name: String,
+    pub path: PathBuf,
+    pub hits: usize,
+}
+
+/// Where the commands run so far were found in `PATH`, like bash's hash table, so `PATH` isn't
+/// searched again for them. `hash -p` can point a name anywhere.
+#[derive(Clone, Default)]
+pub struct CommandHash {
+    entries: Vec<HashedCommand>,
+    /// `PATH` when the entries were looked up.
+    path: Option<OsString>,
+}
+
+impl CommandHash {
+    pub fn entries(&self) -> &[HashedCommand] {
+        &self.entries
+    }
+
+    /// The remembered path of `name`, as long as it's still an executable file.
+    pub fn get(&self, name: &str) -> Option<PathBuf> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.path.clone())
+            .filter(|path| path.is_file() && path.is_executable())
+    }
+
+    /// Remembers `path` for `name`, starting over the hit count if it was elsewhere before.
+    pub fn insert(&mut self, name: &str, path: &Path) {
+        self.sync_path();
+        match self.entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) if entry.path == path => {}
+            Some(entry) => {
+                entry.path = path.to_path_buf();
+                entry.hits = 0;
+            }
+            None => self.entries.push(HashedCommand {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                hits: 0,
+            }),
+        }
+    }
+
+    /// Counts a run of `name` from `path`.
+    pub fn hit(&mut self, name: &str, path: &Path) {
+        self.insert(name, path);
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) {
+            entry.hits += 1;
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Forgets everything once `PATH` changed, as assigning `PATH` does in bash.
+    pub fn sync_path(&mut self) {
+        let path = env::var_os("PATH");
+        if path != self.path {
+            self.entries.clear();
+            self.path = path;
+        }
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::Path,
+    process::Command,
+};
+
+use is_executable::IsExecutable;
+
+use crate::{
+    os,
+    parser::{self, Token, TokenKind, WordPart},
+    shell,
+};
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 8;
+const PENALTY_GAP: i64 = 3;
+
+pub struct Candidate {
+    /// What is shown when listing the possibilities.
+    pub display: String,
+    /// Raw text that replaces the word being completed (already escaped or quoted).
+    pub replacement: String,
+    /// Directories keep the word open (trailing `/`, no closing quote and no space).
+    pub is_dir: bool,
+    /// Shown next to the candidate in the completion menu.
+    pub description: Option<String>,
+}
+
+pub struct Completion {
+    /// Byte position in the line where the word being completed starts.
+    pub start: usize,
+    /// Raw text of the word being completed, as typed.
+    pub word: String,
+    pub quote: Option<char>,
+    pub candidates: Vec<Candidate>,
+}
+
+impl Completion {
+    /// Text inserted for a unique match: closes the quote and adds a space unless it is a directory.
+    pub fn finish(&self, candidate: &Candidate) -> String {
+        let mut replacement = candidate.replacement.clone();
+
+        if !candidate.is_dir {
+            if let Some(quote) = self.quote {
+                replacement.push(quote);
+            }
+            replacement.push(' ');
+        }
+
+        replacement
+    }
+}
+
+/// How the arguments of a command are completed, as registered with the `complete` builtin.
+#[derive(Clone, Default, PartialEq)]
+pub struct CompletionSpec {
+    /// `-W`: fixed word list.
+    pub words: Vec<String>,
+    /// `-C`: external command printing one candidate per line.
+    pub command: Option<String>,
+    /// `-F`: completion function. The shell has no functions, so this names a program that is
+    /// called the way bash calls a function: same arguments and `COMP_*` variables.
+    pub function: Option<String>,
+    /// `-d`: directory names.
+    pub directories: bool,
+    /// `-f`: file names.
+    pub files: bool,
+    /// `-c`: command names.
+    pub commands: bool,
+    /// Menu descriptions for some of the words, used by the builtin completers.
+    pub descriptions: HashMap<String, String>,
+}
+
+impl CompletionSpec {
+    /// Renders the spec the way `complete -p` prints it, so it can be reused as input.
+    pub fn describe(&self, name: &str) -> String {
+        let mut description = String::from("complete");
+
+        if self.directories {
+            description.push_str(" -d");
+        }
+        if self.files {
+            description.push_str(" -f");
+        }
+        if self.commands {
+            description.push_str(" -c");
+        }
+        if !self.words.is_empty() {
+            description.push_str(&format!(" -W '{}'", self.words.join(" ")));
+        }
+        if let Some(function) = &self.function {
+            description.push_str(&format!(" -F {function}"));
+        }
+        if let Some(command) = &self.command {
+            description.push_str(&format!(" -C '{command}'"));
+        }
+
+        format!("{description} {name}")
+    }
+}
+
+/// Completion specs keyed by command name.
+#[derive(Clone, Default)]
+pub struct CompletionRegistry {
+    specs: HashMap<String, CompletionSpec>,
+}
+
+impl CompletionRegistry {
+    /// Registry with the completers for the shell's own builtins.
+    pub fn new() -> CompletionRegistry {
+        let mut registry = CompletionRegistry::default();
+
+        registry.insert(
+            "cd",
+            CompletionSpec {
+                directories: true,
+                ..Default::default()
+            },
+        );
+        registry.insert(
+            "type",
+            CompletionSpec {
+                commands: true,
+                ..Default::default()
+            },
+        );
+        registry.insert(
+            "history",
+            CompletionSpec {
+                words: vec!["-r".to_string(), "-w".to_string(), "-a".to_string()],
+                files: true,
+                descriptions: HashMap::from([
+                    ("-r".to_string(), "read the history file".to_string()),
+                    ("-w".to_string(), "write the history to a file".to_string()),
+                    ("-a".to_string(), "append new lines to a file".to_string()),
+                ]),
+                ..Default::default()
+            },
+        );
+
+        registry
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CompletionSpec> {
+        self.specs.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, spec: CompletionSpec) {
+        self.specs.insert(name.to_string(), spec);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<CompletionSpec> {
+        self.specs.remove(name)
+    }
+
+    pub fn clear(&mut self) {
+        self.specs.clear();
+    }
+
+    /// All specs, sorted by command name.
+    pub fn entries(&self) -> Vec<(&String, &CompletionSpec)> {
+        let mut entries: Vec<(&String, &CompletionSpec)> = self.specs.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PathFilter {
+    All,
+    Directories,
+    Executables,
+}
+
+/// Word under the cursor, split into its raw text and its unescaped value.
+struct Word {
+    start: usize,
+    raw: String,
+    text: String,
+    quote: Option<char>,
+    /// Raw byte length of the word up to (and including) its last `/`.
+    raw_dir_len: usize,
+    /// Unescaped length of the word up to (and including) its last `/`.
+    text_dir_len: usize,
+    /// Raw byte position where the still open quote starts.
+    quote_start: usize,
+    /// Words before this one in the same command (after the last `|` or new line).
+    previous: Vec<String>,
+}
+
+impl Word {
+    fn is_command(&self) -> bool {
+        self.previous.is_empty()
+    }
+
+    /// The open quote has to be written again when it started after the directory part.
+    fn opening(&self) -> String {
+        match self.quote {
+            Some(quote) if self.quote_start >= self.raw_dir_len => quote.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+pub fn complete(
+    line: &str,
+    cursor_pos: usize,
+    know_commands: &HashSet<String>,
+    completions: &CompletionRegistry,
+    current_dir: &Path,
+) -> Completion {
+    let word = current_word(&line[..cursor_pos]);
+    let spec = word.previous.first().and_then(|name| completions.get(name));
+
+    let mut candidates = if word.is_command() && !word.text.contains('/') {
+        complete_commands(&word, know_commands)
+    } else if word.is_command() {
+        complete_paths(&word, current_dir, PathFilter::Executables)
+    } else if let Some(spec) = spec {
+        complete_spec(&word, spec, line, cursor_pos, know_commands, current_dir)
+    } else if word.text.starts_with('$') && !word.text.contains('/') {
+        complete_variables(&word)
+    } else if word.text.starts_with('~') && !word.text.contains('/') {
+        complete_users(&word)
+    } else {
+        complete_paths(&word, current_dir, PathFilter::All)
+    };
+
+    if word.is_command() && candidates.is_empty() && shell::option_enabled("fuzzy_complete") {
+        candidates = complete_commands_fuzzy(&word, know_commands);
+    } else {
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+    }
+
+    Completion {
+        start: word.start,
+        word: word.raw,
+        quote: word.quote,
+        candidates,
+    }
+}
+
+/// The word ending at the end of `line` (the text before the cursor), read with the same
+/// tokenizer that runs the line, or an empty word when the line ends between words.
+fn current_word(line: &str) -> Word {
+    let mut tokens = parser::tokenize(line);
+    let token = tokens
+        .pop_if(|token| token.kind == TokenKind::Word && token.span.end == line.len())
+        .unwrap_or_else(|| Token {
+            kind: TokenKind::Word,
+            span: line.len()..line.len(),
+            value: String::new(),
+            parts: Vec::new(),
+        });
+
+    // only a pipe or a new line starts another command, as when the line runs
+    let command_start = tokens
+        .iter()
+        .rposition(|token| {
+            token.kind == TokenKind::Operator && matches!(token.value.as_str(), "|" | "\n")
+        })
+        .map_or(0, |position| position + 1);
+    let previous = tokens[command_start..]
+        .iter()
+        .filter(|token| matches!(token.kind, TokenKind::Word | TokenKind::Operator))
+        .map(|token| token.value.clone())
+        .collect();
+
+    let raw = &line[token.span.clone()];
+    let open_quote = token.parts.iter().find_map(|(range, part)| {
+        let quote = line[range.start..].chars().next()?;
+        (*part == WordPart::Unterminated && quote != '\\').then_some((quote, range.start))
+    });
+
+    Word {
+        start: token.span.start,
+        raw: raw.to_string(),
+        text: token.value.clone(),
+        quote: open_quote.map(|(quote, _)| quote),
+        raw_dir_len: raw.rfind('/').map_or(0, |slash| slash + 1),
+        text_dir_len: token.value.rfind('/').map_or(0, |slash| slash + 1),
+        quote_start: open_quote.map_or(0, |(_, start)| start - token.span.start),
+        previous,
+    }
+}
+
+fn complete_commands(word: &Word, know_commands: &HashSet<String>) -> Vec<Candidate> {
+    know_commands
+        .iter()
+        .filter(|name| name.starts_with(&word.text))
+        .map(|name| word_candidate(word, name))
+        .collect()
+}
+
+/// Commands containing the typed characters in order, best matches first.
+fn complete_commands_fuzzy(word: &Word, know_commands: &HashSet<String>) -> Vec<Candidate> {
+    let mut scored: Vec<(i64, &String)> = know_commands
+        .iter()
+        .filter_map(|name| fuzzy_score(&word.text, name).map(|score| (score, name)))
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(a.1.len().cmp(&b.1.len()))
+            .then(a.1.cmp(b.1))
+    });
+
+    scored
+        .into_iter()
+        .map(|(_, name)| word_candidate(word, name))
+        .collect()
+}
+
+/// Scores `name` as a subsequence match of `pattern`, or `None` if it doesn't match.
+/// Consecutive characters and characters starting a word (after `-`, `_`, `.`, `/`, a space or
+/// a lower-to-upper case change) score higher; gaps and a late first match score lower.
+/// Matching ignores case unless the pattern has an uppercase letter.
+fn fuzzy_score(pattern: &str, name: &str) -> Option<i64> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let case_sensitive = pattern.iter().any(|c| c.is_uppercase());
+    let equals = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+    let boundary = |position: usize| -> i64 {
+        let is_boundary = position == 0
+            || matches!(name[position - 1], '-' | '_' | '.' | '/' | ' ')
+            || (name[position - 1].is_lowercase() && name[position].is_uppercase());
+        if is_boundary { BONUS_BOUNDARY } else { 0 }
+    };
+
+    // best[j]: best score for the pattern so far with its last character matched at name[j]
+    let mut best: Vec<Option<i64>> = vec![None; name.len()];
+
+    for (i, &pattern_char) in pattern.iter().enumerate() {
+        let mut current: Vec<Option<i64>> = vec![None; name.len()];
+        let mut best_before_gap: Option<i64> = None;
+
+        for j in 0..name.len() {
+            if j >= 2 {
+                best_before_gap = best_before_gap.max(best[j - 2]);
+            }
+
+            if !equals(pattern_char, name[j]) {
+                continue;
+            }
+
+            let score = if i == 0 {
+                Some(-(j.min(3) as i64))
+            } else {
+                let consecutive = if j >= 1 {
+                    best[j - 1].map(|s| s + BONUS_CONSECUTIVE)
+                } else {
+                    None
+                };
+                consecutive.max(best_before_gap.map(|s| s - PENALTY_GAP))
+            };
+
+            current[j] = score.map(|s| s + SCORE_MATCH + boundary(j));
+        }
+
+        best = current;
+    }
+
+    best.into_iter().flatten().max()
+}
+
+fn complete_spec(
+    word: &Word,
+    spec: &CompletionSpec,
+    line: &str,
+    cursor_pos: usize,
+    know_commands: &HashSet<String>,
+    current_dir: &Path,
+) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = spec
+        .words
+        .iter()
+        .filter(|name| name.starts_with(&word.text))
+        .map(|name| Candidate {
+            description: spec.descriptions.get(name).cloned(),
+            ..word_candidate(word, name)
+        })
+        .collect();
+
+    if spec.commands {
+        candidates.append(&mut complete_commands(word, know_commands));
+    }
+
+    if spec.files {
+        candidates.append(&mut complete_paths(word, current_dir, PathFilter::All));
+    } else if spec.directories {
+        candidates.append(&mut complete_paths(word, current_dir, PathFilter::Directories));
+    }
+
+    if let Some(function) = &spec.function {
+        candidates.append(&mut complete_external(word, function, line, cursor_pos, current_dir));
+    }
+    if let Some(command) = &spec.command {
+        candidates.append(&mut complete_external(word, command, line, cursor_pos, current_dir));
+    }
+
+    candidates
+}
+
+/// Runs a `complete -C` command with bash's arguments (command, word, previous word) and
+/// `COMP_*` variables, taking every output line as a candidate. A tab separates an optional
+/// description, as in fish.
+fn complete_external(
+    word: &Word,
+    command: &str,
+    line: &str,
+    cursor_pos: usize,
+    current_dir: &Path,
+) -> Vec<Candidate> {
+    let program = parser::parse_input(command);
+    let Some(program_name) = program.first() else {
+        return Vec::new();
+    };
+
+    let mut comp_words = word.previous.clone();
+    comp_words.push(word.text.clone());
+
+    let result = Command::new(program_name)
+        .args(&program[1..])
+        .arg(comp_words.first().map(String::as_str).unwrap_or_default())
+        .arg(&word.text)
+        .arg(word.previous.last().map(String::as_str).unwrap_or_default())
+        .env("COMP_LINE", line)
+        .env("COMP_POINT", cursor_pos.to_string())
+        .env("COMP_WORDS", comp_words.join(" "))
+        .env("COMP_CWORD", word.previous.len().to_string())
+        .current_dir(current_dir)
+        .output();
+
+    let Ok(output) = result else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('\t') {
+            Some((name, description)) => Candidate {
+                description: Some(description.to_string()),
+                ..word_candidate(word, name)
+            },
+            None => word_candidate(word, line),
+        })
+        .collect()
+}
+
+fn complete_variables(word: &Word) -> Vec<Candidate> {
+    let prefix = &word.text[1..];
+
+    env::vars()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, value)| {
+            let is_dir = Path::new(&value).is_dir();
+            Candidate {
+                display: format!("${name}"),
+                replacement: format!("${name}{}", if is_dir { "/" } else { "" }),
+                is_dir,
+                description: None,
+            }
+        })
+        .collect()
+}
+
+fn complete_users(word: &Word) -> Vec<Candidate> {
+    let prefix = &word.text[1..];
+
+    os::user_home_dirs()
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, _)| Candidate {
+            display: format!("~{name}"),
+            replacement: format!("~{name}/"),
+            is_dir: true,
+            description: None,
+        })
+        .collect()
+}
+
+fn complete_paths(word: &Word, current_dir: &Path, filter: PathFilter) -> Vec<Candidate> {
+    let dir_text = &word.text[..word.text_dir_len];
+    let file_prefix = &word.text[word.text_dir_len..];
+    let raw_dir = &word.raw[..word.raw_dir_len];
+    let opening = word.opening();
+
+    let Ok(directory) = parser::parse_path(&expand_prefix(dir_text), current_dir) else {
+        return Vec::new();
+    };
+    let Ok(read_dir_value) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+
+    for entry in read_dir_value {
+        let Ok(entry_result) = entry else {
+            continue;
+        };
+
+        let name = entry_result.file_name().to_string_lossy().to_string();
+        if !name.starts_with(file_prefix) || (name.starts_with('.') && !file_prefix.starts_with('.'))
+        {
+            continue;
+        }
+
+        let file_path = entry_result.path();
+        let is_dir = file_path.is_dir();
+        let accepted = match filter {
+            PathFilter::All => true,
+            PathFilter::Directories => is_dir,
+            PathFilter::Executables => is_dir || file_path.is_executable(),
+        };
+        if !accepted {
+            continue;
+        }
+
+        let suffix = if is_dir { "/" } else { "" };
+        candidates.push(Candidate {
+            display: format!("{name}{suffix}"),
+            replacement: format!("{raw_dir}{}{suffix}", escape(&name, word.quote, &opening)),
+            is_dir,
+            description: None,
+        });
+    }
+
+    candidates
+}
+
+fn word_candidate(word: &Word, name: &str) -> Candidate {
+    Candidate {
+        display: name.to_string(),
+        replacement: escape(name, word.quote, &word.opening()),
+        is_dir: false,
+        description: None,
+    }
+}
+
+/// Expands a leading `~`, `~user` or `$VAR` so the directory part of a word can be listed.
+fn expand_prefix(text: &str) -> String {
+    let (head, rest) = match text.find('/') {
+        Some(position) => text.split_at(position),
+        None => (text, ""),
+    };
+
+    if let Some(user) = head.strip_prefix('~')
+        && !user.is_empty()
+        && let Some((_, home)) = os::user_home_dirs().into_iter().find(|(name, _)| name == user)
+    {
+        return format!("{}{rest}", home.display());
+    }
+
+    if let Some(name) = head.strip_prefix('$')
+        && let Ok(value) = env::var(name)
+    {
+        return format!("{value}{rest}");
+    }
+
+    text.to_string()
+}
+
+/// Escapes a name for insertion, either inside the quote the user opened or with backslashes.
+fn escape(name: &str, quote: Option<char>, opening: &str) -> String {
+    match quote {
+        Some('\'') => format!("{opening}{name}"),
+        Some(_) => {
+            let mut escaped = opening.to_string();
+            for character in name.chars() {
+                if matches!(character, '"' | '\\' | '$' | '`') {
+                    escaped.push('\\');
+                }
+                escaped.push(character);
+            }
+            escaped
+        }
+        None => {
+            let mut escaped = String::new();
+            for character in name.chars() {
+                if matches!(
+                    character,
+                    ' ' | '\t' | '\'' | '"' | '\\' | '|' | '&' | ';' | '<' | '>' | '(' | ')' | '$'
+                        | '`' | '*' | '?' | '[' | '#' | '!'
+                ) {
+                    escaped.push('\\');
+                }
+                escaped.push(character);
+            }
+            escaped
+        }
+    }
+}
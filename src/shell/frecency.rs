@@ -0,0 +1,187 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::os;
+
+/// Once the ranks add up to more than this, they are all aged by 1%, and directories that fall
+/// below a rank of 1 are forgotten. Same default as z.sh's `_Z_MAXSCORE`.
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+/// A visited directory: how often it was entered (aged over time) and when it was last.
+pub struct DirectoryEntry {
+    pub path: PathBuf,
+    pub rank: f64,
+    pub last_access: i64,
+}
+
+impl DirectoryEntry {
+    /// The rank weighted by how recently the directory was entered.
+    pub fn frecency(&self, now: i64) -> f64 {
+        let age = now - self.last_access;
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * weight
+    }
+
+    /// Keywords must appear in the path in order, ignoring case, and the last one in its final
+    /// component, so `z foo` matches `~/foo` but not `~/foo/bar`.
+    fn matches(&self, keywords: &[String]) -> bool {
+        let path = self.path.to_string_lossy().to_lowercase();
+        let mut rest = path.as_str();
+        for keyword in keywords {
+            let keyword = keyword.to_lowercase();
+            match rest.find(&keyword) {
+                Some(index) => rest = &rest[index + keyword.len()..],
+                None => return false,
+            }
+        }
+
+        match keywords.last() {
+            Some(last) => self.path.file_name().is_some_and(|name| {
+                name.to_string_lossy()
+                    .to_lowercase()
+                    .contains(&last.to_lowercase())
+            }),
+            None => true,
+        }
+    }
+}
+
+/// Counts a visit to `dir` in the database file, under an advisory lock since other sessions
+/// record their visits to the same file. Nothing is recorded for `$HOME` or without a file.
+pub fn record(dir: &Path) {
+    let Some(path) = database_file() else {
+        return;
+    };
+    if env::var_os("HOME").is_some_and(|home| dir == Path::new(&home)) {
+        return;
+    }
+    // the database is a convenience: failing to update it mustn't get in the way of `cd`
+    let _ = update(&path, dir);
+}
+
+/// Directories matching all `keywords`, best first, leaving out `current_dir` and directories
+/// that no longer exist.
+pub fn query(keywords: &[String], current_dir: &Path) -> Vec<DirectoryEntry> {
+    let Some(path) = database_file() else {
+        return Vec::new();
+    };
+    let Ok(entries) = read_database(&path) else {
+        return Vec::new();
+    };
+
+    let now = os::now();
+    let mut matches: Vec<DirectoryEntry> = entries
+        .into_iter()
+        .filter(|entry| entry.path != current_dir && entry.matches(keywords) && entry.path.is_dir())
+        .collect();
+    matches.sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+    matches
+}
+
+fn update(path: &Path, dir: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .open(path)?;
+    file.lock()?;
+
+    let mut entries = parse_entries(&read_all(&mut file)?);
+    // forget directories that were removed since
+    entries.retain(|entry| entry.path.is_dir());
+
+    let now = os::now();
+    match entries.iter_mut().find(|entry| entry.path == dir) {
+        Some(entry) => {
+            entry.rank += 1.0;
+            entry.last_access = now;
+        }
+        None => entries.push(DirectoryEntry {
+            path: dir.to_path_buf(),
+            rank: 1.0,
+            last_access: now,
+        }),
+    }
+
+    if entries.iter().map(|entry| entry.rank).sum::<f64>() > MAX_TOTAL_RANK {
+        for entry in &mut entries {
+            entry.rank *= 0.99;
+        }
+        entries.retain(|entry| entry.rank >= 1.0);
+    }
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(format_entries(&entries).as_bytes())
+}
+
+fn read_database(path: &Path) -> io::Result<Vec<DirectoryEntry>> {
+    let mut file = File::open(path)?;
+    file.lock_shared()?;
+    Ok(parse_entries(&read_all(&mut file)?))
+}
+
+fn read_all(file: &mut File) -> io::Result<String> {
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// One `path|rank|time` line per directory, the format of z.sh's `~/.z`.
+fn parse_entries(content: &str) -> Vec<DirectoryEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let last_access = fields.next()?.parse().ok()?;
+            let rank = fields.next()?.parse().ok()?;
+            let path = PathBuf::from(fields.next()?);
+            Some(DirectoryEntry {
+                path,
+                rank,
+                last_access,
+            })
+        })
+        .collect()
+}
+
+fn format_entries(entries: &[DirectoryEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{}|{}|{}\n",
+                entry.path.display(),
+                entry.rank,
+                entry.last_access
+            )
+        })
+        .collect()
+}
+
+/// `_Z_DATA`, or `~/.z` like z.sh.
+fn database_file() -> Option<PathBuf> {
+    match env::var_os("_Z_DATA").filter(|path| !path.is_empty()) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(|home| PathBuf::from(home).join(".z")),
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashSet, path::Path};
+
+use is_executable::IsExecutable;
+use owo_colors::Style;
+
+use crate::{
+    os,
+    parser::{self, TokenKind, WordPart},
+};
+
+/// Colors the input line as it will be parsed: the command word is green when it runs something
+/// and red when it doesn't, and strings, variables, operators and comments get their own color.
+pub struct Highlighter<'a> {
+    know_commands: &'a HashSet<String>,
+    current_dir: &'a Path,
+    /// Executables of the current directory, which run by name too. Read once per prompt
+    /// rather than on every keystroke.
+    local_commands: HashSet<String>,
+}
+
+impl<'a> Highlighter<'a> {
+    pub fn new(know_commands: &'a HashSet<String>, current_dir: &'a Path) -> Highlighter<'a> {
+        Highlighter {
+            know_commands,
+            current_dir,
+            local_commands: os::executable_names(current_dir),
+        }
+    }
+
+    /// The input with color codes, newlines followed by `continuation` (the `PS2` prompt).
+    pub fn paint(&self, input: &str, continuation: &str) -> String {
+        let mut styles: Vec<Style> = vec![Style::new(); input.len()];
+        let mut command_position = true;
+        let mut after_redirect = false;
+
+        for token in parser::tokenize(input) {
+            let style = match token.kind {
+                TokenKind::Word if after_redirect => None,
+                TokenKind::Word if command_position => {
+                    let resolves = self.resolves(&token.value);
+                    Some(if resolves { Style::new().green() } else { Style::new().red() })
+                }
+                TokenKind::Word => None,
+                TokenKind::Operator | TokenKind::Redirect => Some(Style::new().cyan()),
+                TokenKind::Comment => Some(Style::new().bright_black()),
+            };
+
+            if let Some(style) = style {
+                styles[token.span.clone()].fill(style);
+            }
+
+            for (range, part) in &token.parts {
+                let part_style = match part {
+                    // quotes in the command word keep the command color
+                    WordPart::Quoted if command_position && !after_redirect => continue,
+                    WordPart::Quoted => Style::new().yellow(),
+                    WordPart::Variable => Style::new().magenta(),
+                    WordPart::Unterminated => Style::new().red().underline(),
+                };
+                styles[range.clone()].fill(part_style);
+            }
+
+            match token.kind {
+                TokenKind::Word if after_redirect => after_redirect = false,
+                TokenKind::Word => command_position = false,
+                // only a pipe or a new line starts another command: `;`, `&&` and `||` are passed
+                // to the command as arguments
+                TokenKind::Operator => {
+                    command_position = matches!(token.value.as_str(), "|" | "\n")
+                }
+                TokenKind::Redirect => after_redirect = true,
+                TokenKind::Comment => {}
+            }
+        }
+
+        // print runs of characters sharing a style, with newlines left out of the colors
+        let mut painted = String::new();
+        let mut run_start = 0;
+        for (index, character) in input.char_indices() {
+            if character == '\n' {
+                painted.push_str(&styles[run_start].style(&input[run_start..index]).to_string());
+                painted.push_str(&format!("\r\n{continuation}"));
+                run_start = index + 1;
+            } else if styles[index] != styles[run_start] {
+                painted.push_str(&styles[run_start].style(&input[run_start..index]).to_string());
+                run_start = index;
+            }
+        }
+        if run_start < input.len() {
+            painted.push_str(&styles[run_start].style(&input[run_start..]).to_string());
+        }
+
+        painted
+    }
+
+    fn resolves(&self, name: &str) -> bool {
+        if name.contains('/') {
+            return parser::parse_path(name, self.current_dir)
+                .is_ok_and(|path| path.is_file() && path.is_executable());
+        }
+
+        self.know_commands.contains(name) || self.local_commands.contains(name)
+    }
+}
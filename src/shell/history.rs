@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+    slice,
+};
+
+use crate::{os, parser, shell};
+
+/// Entries kept in memory when `HISTSIZE` isn't set (bash's default).
+const DEFAULT_HISTSIZE: usize = 500;
+
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub line: String,
+    /// When the line was entered, in seconds since the epoch, if known.
+    pub timestamp: Option<i64>,
+    /// Tells apart the lines recorded in this session (0 for lines read from a file), so a
+    /// line can still be found after entries before it were deleted.
+    id: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(line: &str, timestamp: Option<i64>) -> HistoryEntry {
+        HistoryEntry {
+            line: line.to_string(),
+            timestamp,
+            id: 0,
+        }
+    }
+}
+
+/// Command lines entered in this shell, limited by `HISTSIZE` and filtered by `HISTCONTROL`
+/// and `HISTIGNORE`. Each recorded line is appended to `HISTFILE` right away, under an advisory
+/// lock, so several sessions can share one file without overwriting each other.
+#[derive(Clone, Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    /// Entries dropped from the front to stay within `HISTSIZE`, so the first entry is number `base + 1`.
+    base: usize,
+    /// Bytes of `HISTFILE` this session has already seen, its own appends included.
+    file_offset: u64,
+    /// Lines other sessions appended to `HISTFILE` before this session's last append, not merged
+    /// yet. Only the last `HISTSIZE` of them are kept.
+    unread: Vec<HistoryEntry>,
+    /// Id of the line this session last appended to `HISTFILE`, where it was written and what
+    /// was written.
+    last_append: Option<(u64, u64, String)>,
+    /// Number of the last entry written out by `history -a`.
+    appended: usize,
+    /// Id of the last recorded line.
+    last_id: u64,
+    /// Id of the line being run, if it was recorded (bash's `hist_last_line_added`).
+    current_id: Option<u64>,
+    /// Bytes read so far from other history files, for `history -n`.
+    read_offsets: HashMap<PathBuf, u64>,
+}
+
+impl History {
+    /// Reads `HISTFILE`, keeping its last `HISTSIZE` lines.
+    pub fn load() -> History {
+        let mut history = History::default();
+
+        if let Some(path) = history_file()
+            && let Ok(entries) = read_path(&path, &mut history.file_offset)
+        {
+            history.entries = entries;
+            // lines that don't fit weren't numbered in this session
+            if let Some(limit) = history_size()
+                && history.entries.len() > limit
+            {
+                history.entries.drain(..history.entries.len() - limit);
+            }
+            history.appended = history.entries.len();
+        }
+
+        history
+    }
+
+    /// Trims `HISTFILE` to its last `HISTFILESIZE` lines. Lines were already appended as they
+    /// were entered, so nothing other sessions wrote is lost.
+    pub fn save(&self) {
+        if let Some(path) = history_file()
+            && let Some(limit) = limit("HISTFILESIZE", history_size())
+        {
+            _ = truncate_file(&path, limit);
+        }
+    }
+
+    /// Merges the lines other sessions appended to `HISTFILE` since this one last looked at it
+    /// (the `share_history` option does this before each prompt).
+    pub fn merge_shared(&mut self) {
+        if let Some(path) = history_file() {
+            _ = self.read_new(&path);
+        }
+    }
+
+    /// `history -r`: adds every line of a history file.
+    pub fn read(&mut self, path: &Path) -> io::Result<()> {
+        let mut offset = 0;
+        let entries = read_path(path, &mut offset)?;
+        self.read_offsets.insert(path.to_path_buf(), offset);
+        self.extend(entries);
+        Ok(())
+    }
+
+    /// `history -n`: adds the lines of a history file this session hasn't read yet. For
+    /// `HISTFILE` these are the lines other sessions appended.
+    pub fn read_new(&mut self, path: &Path) -> io::Result<()> {
+        let is_history_file = history_file().as_deref() == Some(path);
+        let mut entries = Vec::new();
+
+        let result = if is_history_file {
+            entries = mem::take(&mut self.unread);
+            read_path(path, &mut self.file_offset)
+        } else {
+            read_path(path, self.read_offsets.entry(path.to_path_buf()).or_default())
+        };
+        if let Ok(new_entries) = &result {
+            entries.extend_from_slice(new_entries);
+        }
+
+        self.extend(entries);
+        result.map(|_| ())
+    }
+
+    /// `history -w`: replaces a history file with the whole history.
+    pub fn write(&mut self, path: &Path) -> io::Result<()> {
+        write_file(path, &self.entries, false)?;
+        if history_file().as_deref() == Some(path) {
+            self.file_offset = fs::metadata(path)?.len();
+            self.unread.clear();
+        }
+        Ok(())
+    }
+
+    /// `history -a`: appends the entries added since the last `history -a` (or since the session
+    /// started). Recorded lines are already in `HISTFILE`, so for it they're only marked.
+    pub fn append(&mut self, path: &Path) -> io::Result<()> {
+        let start = self.appended.saturating_sub(self.base).min(self.entries.len());
+        if start < self.entries.len() && history_file().as_deref() != Some(path) {
+            write_file(path, &self.entries[start..], true)?;
+        }
+        self.appended = self.base + self.entries.len();
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Number shown for the first entry by `history` (numbers keep growing as old entries are dropped).
+    pub fn first_number(&self) -> usize {
+        self.base + 1
+    }
+
+    /// Number the next recorded line will get.
+    pub fn next_number(&self) -> usize {
+        self.base + self.entries.len() + 1
+    }
+
+    /// Records a line entered by the user, unless `HISTCONTROL` or `HISTIGNORE` say otherwise.
+    pub fn record(&mut self, line: &str) {
+        self.current_id = None;
+        if line.trim().is_empty() {
+            return;
+        }
+
+        let control = env::var("HISTCONTROL").unwrap_or_default();
+        let control: Vec<&str> = control.split(':').collect();
+        let ignore_space = control.iter().any(|c| matches!(*c, "ignorespace" | "ignoreboth"));
+        let ignore_dups = control.iter().any(|c| matches!(*c, "ignoredups" | "ignoreboth"));
+
+        if ignore_space && line.starts_with(' ') {
+            return;
+        }
+        if ignore_dups && self.entries.last().is_some_and(|last| last.line == line) {
+            return;
+        }
+        if self.is_ignored(line) {
+            return;
+        }
+        if control.contains(&"erasedups") {
+            self.entries.retain(|entry| entry.line != line);
+        }
+
+        self.last_id += 1;
+        let entry = HistoryEntry {
+            id: self.last_id,
+            ..HistoryEntry::new(line, Some(os::now()))
+        };
+        _ = self.append_to_file(&entry);
+        // keep the order of the file when sharing it
+        if shell::option_enabled("share_history") {
+            let unread = mem::take(&mut self.unread);
+            self.entries.extend(unread);
+        }
+        self.entries.push(entry);
+        self.trim();
+        self.current_id = Some(self.last_id);
+    }
+
+    /// `history -c`: removes every entry, numbering starts again from 1.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.base = 0;
+        self.appended = 0;
+    }
+
+    /// `history -d`: removes the entries numbered `first` to `last`, returning false when the
+    /// range isn't in the history.
+    pub fn delete(&mut self, first: usize, last: usize) -> bool {
+        if first < self.first_number() || last >= self.next_number() || first > last {
+            return false;
+        }
+
+        self.entries.drain(first - self.first_number()..=last - self.first_number());
+        // entries after the range moved down, keep `history -a` from skipping them
+        if self.appended >= first {
+            self.appended = self.appended.saturating_sub(last.min(self.appended) - first + 1);
+        }
+        true
+    }
+
+    /// `history -s`: adds `line` as an entry, in `HISTFILE` too. Like in bash, it replaces the
+    /// `history -s` line itself when that line was recorded; otherwise nothing is removed.
+    pub fn store(&mut self, line: &str) {
+        let entry = HistoryEntry::new(line, Some(os::now()));
+        let recorded = self
+            .current_id
+            .take()
+            .filter(|id| self.entries.last().is_some_and(|last| last.id == *id));
+
+        let replaced = match recorded {
+            Some(id) => {
+                self.entries.pop();
+                matches!(self.replace_in_file(id, &entry), Ok(true))
+            }
+            None => false,
+        };
+        if !replaced {
+            _ = self.append_to_file(&entry);
+        }
+        self.entries.push(entry);
+        self.trim();
+    }
+
+    /// Replaces the line with this id in `HISTFILE` with `entry`, keeping what other sessions
+    /// appended after it. Returns false unless it is the line this session appended last and
+    /// it is still there.
+    fn replace_in_file(&mut self, id: u64, entry: &HistoryEntry) -> io::Result<bool> {
+        let Some(path) = history_file() else {
+            return Ok(false);
+        };
+        let Some((_, start, written)) = self.last_append.take_if(|(last, _, _)| *last == id) else {
+            return Ok(false);
+        };
+
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        file.lock()?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+
+        let start = start as usize;
+        let end = start + written.len();
+        // another session rewrote or trimmed the file since
+        if content.get(start..end) != Some(written.as_bytes()) {
+            return Ok(false);
+        }
+
+        let replacement = format_entries(slice::from_ref(entry), timestamps_enabled());
+        content.splice(start..end, replacement.bytes());
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&content)?;
+
+        if self.file_offset >= end as u64 {
+            self.file_offset = self.file_offset + replacement.len() as u64 - written.len() as u64;
+        }
+        self.last_append = Some((entry.id, start as u64, replacement));
+        Ok(true)
+    }
+
+    /// Appends a new entry to `HISTFILE`, first setting aside what other sessions wrote since
+    /// this one last read it, so those lines can still be merged later.
+    fn append_to_file(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        let Some(path) = history_file() else {
+            return Ok(());
+        };
+
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        file.lock()?;
+        let unread = read_from(&mut file, &mut self.file_offset)?;
+        self.unread.extend(unread);
+        // merging trims to `HISTSIZE` anyway, so older unread lines would never be kept
+        if let Some(limit) = history_size()
+            && self.unread.len() > limit
+        {
+            self.unread.drain(..self.unread.len() - limit);
+        }
+
+        let content = format_entries(slice::from_ref(entry), timestamps_enabled());
+        file.write_all(content.as_bytes())?;
+        let start = self.file_offset;
+        self.file_offset += content.len() as u64;
+        self.last_append = Some((entry.id, start, content));
+        Ok(())
+    }
+
+    /// Whether a `HISTIGNORE` pattern matches the whole line; `&` stands for the previous entry.
+    fn is_ignored(&self, line: &str) -> bool {
+        let Ok(patterns) = env::var("HISTIGNORE") else {
+            return false;
+        };
+
+        split_patterns(&patterns).iter().any(|pattern| {
+            if pattern == "&" {
+                self.entries.last().is_some_and(|last| last.line == line)
+            } else {
+                !pattern.is_empty() && parser::glob_match(pattern, line)
+            }
+        })
+    }
+
+    /// Adds entries read from a file, as they are.
+    fn extend(&mut self, entries: Vec<HistoryEntry>) {
+        self.entries.extend(entries);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if let Some(limit) = history_size()
+            && self.entries.len() > limit
+        {
+            let dropped = self.entries.len() - limit;
+            self.entries.drain(..dropped);
+            self.base += dropped;
+        }
+    }
+}
+
+/// Reads a history file from `offset`. A `#<epoch>` comment line gives the time of the line after
+/// it, as bash writes them when `HISTTIMEFORMAT` is set; files without them work the same.
+fn read_path(path: &Path, offset: &mut u64) -> io::Result<Vec<HistoryEntry>> {
+    let mut file = File::open(path)?;
+    file.lock_shared()?;
+    read_from(&mut file, offset)
+}
+
+/// Writes (or appends) entries to a history file, with `#<epoch>` lines when `HISTTIMEFORMAT` is set.
+fn write_file(path: &Path, entries: &[HistoryEntry], append: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).write(true).append(append).open(path)?;
+    file.lock()?;
+    if !append {
+        file.set_len(0)?;
+    }
+    file.write_all(format_entries(entries, timestamps_enabled()).as_bytes())
+}
+
+/// Reads the entries after `offset` and moves `offset` to the end of the file.
+fn read_from(file: &mut File, offset: &mut u64) -> io::Result<Vec<HistoryEntry>> {
+    // another session trimmed the file: what's left of it was already seen
+    if file.metadata()?.len() < *offset {
+        *offset = file.metadata()?.len();
+        return Ok(Vec::new());
+    }
+
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(*offset))?;
+    file.read_to_string(&mut content)?;
+    *offset += content.len() as u64;
+    Ok(parse_entries(&content))
+}
+
+/// Keeps the last `limit` entries of a history file, along with their timestamps.
+fn truncate_file(path: &Path, limit: usize) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    file.lock()?;
+
+    let entries = read_from(&mut file, &mut 0)?;
+    if entries.len() <= limit {
+        return Ok(());
+    }
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(format_entries(&entries[entries.len() - limit..], true).as_bytes())
+}
+
+/// Parses history file lines. An entry spans several lines, as bash writes them with `lithist`,
+/// while it leaves a quote open or ends with a `\`, just like when it was typed.
+fn parse_entries(content: &str) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    let mut timestamp = None;
+    let mut continued = false;
+
+    for line in content.lines() {
+        if continued && let Some(entry) = entries.last_mut() {
+            entry.line.push('\n');
+            entry.line.push_str(line);
+            continued = parser::is_incomplete(&entry.line);
+        } else if let Some(epoch) = line.strip_prefix('#')
+            && !epoch.is_empty()
+            && epoch.chars().all(|c| c.is_ascii_digit())
+        {
+            timestamp = epoch.parse().ok();
+        } else if !line.is_empty() {
+            entries.push(HistoryEntry::new(line, timestamp.take()));
+            continued = parser::is_incomplete(line);
+        }
+    }
+
+    entries
+}
+
+fn format_entries(entries: &[HistoryEntry], timestamps: bool) -> String {
+    let mut content = String::new();
+
+    for entry in entries {
+        if timestamps && let Some(timestamp) = entry.timestamp {
+            content.push_str(&format!("#{timestamp}\n"));
+        }
+        content.push_str(&entry.line);
+        content.push('\n');
+    }
+
+    content
+}
+
+fn timestamps_enabled() -> bool {
+    env::var_os("HISTTIMEFORMAT").is_some()
+}
+
+fn history_file() -> Option<PathBuf> {
+    env::var_os("HISTFILE").filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+fn history_size() -> Option<usize> {
+    limit("HISTSIZE", Some(DEFAULT_HISTSIZE))
+}
+
+/// Size limit from a variable, `default` when unset, and no limit when negative or not a number.
+fn limit(variable: &str, default: Option<usize>) -> Option<usize> {
+    match env::var(variable) {
+        Ok(value) => value.trim().parse::<usize>().ok(),
+        Err(_) => default,
+    }
+}
+
+/// Splits `HISTIGNORE` on colons, where `\:` is a literal colon.
+fn split_patterns(value: &str) -> Vec<String> {
+    let mut patterns = vec![String::new()];
+    let mut characters = value.chars();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some(':') => patterns.last_mut().unwrap().push(':'),
+                Some(other) => {
+                    let pattern = patterns.last_mut().unwrap();
+                    pattern.push('\\');
+                    pattern.push(other);
+                }
+                None => patterns.last_mut().unwrap().push('\\'),
+            },
+            ':' => patterns.push(String::new()),
+            other => patterns.last_mut().unwrap().push(other),
+        }
+    }
+
+    patterns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.line.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_plain_and_timestamped_lines() {
+        let entries = parse_entries("ls\n#1700000000\ncd /tmp\n\n#12ab\n");
+
+        assert_eq!(lines(&entries), ["ls", "cd /tmp", "#12ab"]);
+        assert_eq!(entries[0].timestamp, None);
+        assert_eq!(entries[1].timestamp, Some(1_700_000_000));
+    }
+
+    #[test]
+    fn multi_line_entries_survive_a_round_trip() {
+        let entries = [
+            HistoryEntry::new("echo 'one\n\n#1700000000\ntwo' | cat", Some(1_700_000_000)),
+            HistoryEntry::new("echo a \\\nb", Some(1_700_000_001)),
+            HistoryEntry::new("echo \\\\", None),
+            HistoryEntry::new("pwd", None),
+        ];
+
+        for timestamps in [false, true] {
+            let parsed = parse_entries(&format_entries(&entries, timestamps));
+            assert_eq!(lines(&parsed), lines(&entries));
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use crate::{parser, shell::history::HistoryEntry};
+
+/// A line after history expansion.
+pub struct Expansion {
+    pub line: String,
+    /// Whether any history reference was replaced, in which case the line is echoed.
+    pub expanded: bool,
+    /// Set by the `:p` modifier: the line is printed and recorded, but not run.
+    pub print_only: bool,
+}
+
+/// Replaces csh-style history references (`!!`, `!n`, `!-n`, `!prefix`, `!?text?`, `!$`,
+/// `^old^new^`), with optional word designators (`:0`, `:1-3`, `:$`, `:*`) and modifiers
+/// (`:h`, `:t`, `:r`, `:e`, `:s/old/new/`, `:gs/old/new/`, `:p`). Nothing inside single quotes
+/// or after a backslash is expanded. `first_number` is the number of the first entry of `history`,
+/// which `!n` counts from.
+pub fn expand(line: &str, history: &[HistoryEntry], first_number: usize) -> Result<Expansion, String> {
+    let mut expansion = Expansion {
+        line: String::new(),
+        expanded: false,
+        print_only: false,
+    };
+
+    // quick substitution: `^old^new^` is `!!:s/old/new/`
+    if let Some(rest) = line.strip_prefix('^') {
+        let chars: Vec<char> = format!("s^{rest}").chars().collect();
+        let mut text = previous(history, "^")?.to_string();
+        let mut position = 0;
+        apply_modifier(&chars, &mut position, &mut text, &mut expansion)?;
+
+        let rest: String = chars[position..].iter().collect();
+        expansion.line = text + &rest;
+        expansion.expanded = true;
+        return Ok(expansion);
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut position = 0;
+
+    while position < chars.len() {
+        let character = chars[position];
+
+        match character {
+            '\\' if !in_single_quote => {
+                expansion.line.extend(&chars[position..(position + 2).min(chars.len())]);
+                position += 2;
+                continue;
+            }
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '!' if !in_single_quote && starts_reference(chars.get(position + 1), in_double_quote) => {
+                let text = reference(&chars, &mut position, history, first_number, &mut expansion)?;
+                expansion.line.push_str(&text);
+                expansion.expanded = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        expansion.line.push(character);
+        position += 1;
+    }
+
+    Ok(expansion)
+}
+
+/// A `!` stays literal before a blank, `=`, `(`, a quote or operator, or the end of the line.
+fn starts_reference(next: Option<&char>, in_double_quote: bool) -> bool {
+    match next {
+        None | Some(' ' | '\t' | '\n' | '=' | '(' | '\'' | ';' | '|' | '&' | '<' | '>') => false,
+        Some('"') => !in_double_quote,
+        Some(_) => true,
+    }
+}
+
+/// Expands the reference whose `!` is at `position`, leaving `position` right after it.
+fn reference(
+    chars: &[char],
+    position: &mut usize,
+    history: &[HistoryEntry],
+    first_number: usize,
+    expansion: &mut Expansion,
+) -> Result<String, String> {
+    let start = *position;
+    *position += 1;
+
+    let event = match chars[*position] {
+        '!' => {
+            *position += 1;
+            previous(history, "!!")?
+        }
+        // `!$`, `!^`, `!*` and `!:n` refer to the previous command
+        '^' | '$' | '*' | ':' => previous(history, "!")?,
+        '?' => {
+            *position += 1;
+            let end = chars[*position..]
+                .iter()
+                .position(|&c| c == '?' || c == '\n')
+                .map_or(chars.len(), |p| *position + p);
+            let text: String = chars[*position..end].iter().collect();
+            *position = if chars.get(end) == Some(&'?') { end + 1 } else { end };
+
+            history
+                .iter()
+                .rev()
+                .map(|entry| &entry.line)
+                .find(|line| line.contains(&text))
+                .ok_or_else(|| format!("!?{text}: event not found"))?
+        }
+        '-' | '0'..='9' => {
+            let end = chars[*position + 1..]
+                .iter()
+                .position(|c| !c.is_ascii_digit())
+                .map_or(chars.len(), |p| *position + 1 + p);
+            let text: String = chars[*position..end].iter().collect();
+            *position = end;
+
+            let index = match text.parse::<i64>() {
+                Ok(number) if number < 0 => history.len() as i64 + number,
+                Ok(number) => number - first_number as i64,
+                Err(_) => -1,
+            };
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| history.get(index))
+                .map(|entry| &entry.line)
+                .ok_or_else(|| format!("!{text}: event not found"))?
+        }
+        _ => {
+            let end = chars[*position..]
+                .iter()
+                .position(|c| {
+                    c.is_whitespace() || matches!(c, ':' | '"' | '\'' | ';' | '|' | '&' | '<' | '>')
+                })
+                .map_or(chars.len(), |p| *position + p);
+            let prefix: String = chars[*position..end].iter().collect();
+            *position = end;
+
+            history
+                .iter()
+                .rev()
+                .map(|entry| &entry.line)
+                .find(|line| line.starts_with(&prefix))
+                .ok_or_else(|| format!("!{prefix}: event not found"))?
+        }
+    };
+
+    let mut text = event.to_string();
+
+    // word designator, the ':' may be left out before `^`, `$` and `*`
+    let designator_start = match chars.get(*position) {
+        Some('^' | '$' | '*') => Some(*position),
+        Some(':')
+            if chars
+                .get(*position + 1)
+                .is_some_and(|&c| c.is_ascii_digit() || "^$*-".contains(c)) =>
+        {
+            Some(*position + 1)
+        }
+        _ => None,
+    };
+    if let Some(designator_start) = designator_start {
+        *position = designator_start;
+        text = select_words(event, chars, position).ok_or_else(|| {
+            let reference_text: String = chars[start..*position].iter().collect();
+            format!("{reference_text}: bad word specifier")
+        })?;
+    }
+
+    while chars.get(*position) == Some(&':')
+        && chars.get(*position + 1).is_some_and(|c| "htrepsg".contains(*c))
+    {
+        *position += 1;
+        apply_modifier(chars, position, &mut text, expansion)?;
+    }
+
+    Ok(text)
+}
+
+fn previous<'a>(history: &'a [HistoryEntry], reference: &str) -> Result<&'a String, String> {
+    history.last().map(|entry| &entry.line).ok_or_else(|| format!("{reference}: event not found"))
+}
+
+/// Reads a word designator at `position` and returns the selected words of `event`, joined by spaces.
+fn select_words(event: &str, chars: &[char], position: &mut usize) -> Option<String> {
+    let words: Vec<&str> = parser::tokenize(event)
+        .into_iter()
+        .map(|token| &event[token.span])
+        .collect();
+    let last = words.len().checked_sub(1)?;
+
+    let read_number = |position: &mut usize| -> Option<usize> {
+        let end = chars[*position..]
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .map_or(chars.len(), |p| *position + p);
+        let number = chars[*position..end].iter().collect::<String>().parse().ok();
+        *position = end;
+        number
+    };
+
+    let (first, end) = match chars[*position] {
+        '^' => {
+            *position += 1;
+            (1, 1)
+        }
+        '$' => {
+            *position += 1;
+            (last, last)
+        }
+        // `*` is every argument, and nothing when there are none
+        '*' => {
+            *position += 1;
+            if last == 0 {
+                return Some(String::new());
+            }
+            (1, last)
+        }
+        _ => {
+            let first = if chars[*position] == '-' { 0 } else { read_number(position)? };
+
+            match chars.get(*position) {
+                Some('*') => {
+                    *position += 1;
+                    (first, last)
+                }
+                Some('-') => {
+                    *position += 1;
+                    match chars.get(*position) {
+                        Some('$') => {
+                            *position += 1;
+                            (first, last)
+                        }
+                        Some(c) if c.is_ascii_digit() => (first, read_number(position)?),
+                        // `x-` stops before the last word
+                        _ => (first, last.checked_sub(1)?),
+                    }
+                }
+                _ => (first, first),
+            }
+        }
+    };
+
+    if first > end || end > last {
+        return None;
+    }
+    Some(words[first..=end].join(" "))
+}
+
+/// Applies the modifier at `position` (just after its ':') to `text`.
+fn apply_modifier(
+    chars: &[char],
+    position: &mut usize,
+    text: &mut String,
+    expansion: &mut Expansion,
+) -> Result<(), String> {
+    let modifier = chars[*position];
+    *position += 1;
+
+    match modifier {
+        // head: remove the last path component
+        'h' => {
+            if let Some(slash) = text.rfind('/') {
+                text.truncate(slash.max(1));
+            }
+        }
+        // tail: keep only the last path component
+        't' => {
+            if let Some(slash) = text.rfind('/') {
+                *text = text[slash + 1..].to_string();
+            }
+        }
+        // remove the suffix
+        'r' => {
+            if let Some(dot) = suffix_start(text) {
+                text.truncate(dot);
+            }
+        }
+        // keep only the suffix
+        'e' => {
+            *text = suffix_start(text).map(|dot| text[dot..].to_string()).unwrap_or_default();
+        }
+        'p' => expansion.print_only = true,
+        's' | 'g' => {
+            let global = modifier == 'g';
+            if global && chars.get(*position) != Some(&'s') {
+                return Err(String::from(":g: unrecognized history modifier"));
+            }
+            if global {
+                *position += 1;
+            }
+
+            let Some(&delimiter) = chars.get(*position) else {
+                return Err(String::from(":s: substitution failed"));
+            };
+            *position += 1;
+            let old = substitution_part(chars, position, delimiter);
+            let new = substitution_part(chars, position, delimiter).replace('&', &old);
+
+            if old.is_empty() || !text.contains(&old) {
+                return Err(format!(":s{delimiter}{old}{delimiter}: substitution failed"));
+            }
+            *text = if global {
+                text.replace(&old, &new)
+            } else {
+                text.replacen(&old, &new, 1)
+            };
+        }
+        other => return Err(format!(":{other}: unrecognized history modifier")),
+    }
+
+    Ok(())
+}
+
+/// Text up to the next unescaped `delimiter` (or the end of the line), skipping the delimiter.
+fn substitution_part(chars: &[char], position: &mut usize, delimiter: char) -> String {
+    let mut part = String::new();
+
+    while let Some(&c) = chars.get(*position) {
+        *position += 1;
+        if c == delimiter {
+            break;
+        }
+        if c == '\\' && chars.get(*position) == Some(&delimiter) {
+            part.push(delimiter);
+            *position += 1;
+            continue;
+        }
+        part.push(c);
+    }
+
+    part
+}
+
+/// Position of the `.` starting the suffix of the last path component, if it has one.
+fn suffix_start(text: &str) -> Option<usize> {
+    let name_start = text.rfind('/').map_or(0, |slash| slash + 1);
+    text[name_start..].rfind('.').map(|dot| name_start + dot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<HistoryEntry> {
+        ["ls -l /tmp/file.txt", "git commit -m 'x y'", "echo one two three"]
+            .into_iter()
+            .map(|line| HistoryEntry::new(line, None))
+            .collect()
+    }
+
+    fn expand_line(line: &str) -> Result<String, String> {
+        expand(line, &history(), 1).map(|expansion| expansion.line)
+    }
+
+    #[test]
+    fn event_references() {
+        assert_eq!(expand_line("!!").unwrap(), "echo one two three");
+        assert_eq!(expand_line("!1 | wc").unwrap(), "ls -l /tmp/file.txt | wc");
+        assert_eq!(expand_line("!-2").unwrap(), "git commit -m 'x y'");
+        assert_eq!(expand_line("!gi").unwrap(), "git commit -m 'x y'");
+        assert_eq!(expand_line("!?file?").unwrap(), "ls -l /tmp/file.txt");
+        assert_eq!(expand_line("^two^2^").unwrap(), "echo one 2 three");
+
+        assert_eq!(expand_line("!4").unwrap_err(), "!4: event not found");
+        assert_eq!(expand_line("!-4").unwrap_err(), "!-4: event not found");
+        assert_eq!(expand_line("!cd").unwrap_err(), "!cd: event not found");
+    }
+
+    #[test]
+    fn numbers_count_from_the_first_entry() {
+        let history = history();
+
+        assert_eq!(expand("!11", &history, 11).unwrap().line, "ls -l /tmp/file.txt");
+        assert_eq!(expand("!13", &history, 11).unwrap().line, "echo one two three");
+        let error = expand("!1", &history, 11).map(|expansion| expansion.line).unwrap_err();
+        assert_eq!(error, "!1: event not found");
+    }
+
+    #[test]
+    fn word_designators_and_modifiers() {
+        assert_eq!(expand_line("!$").unwrap(), "three");
+        assert_eq!(expand_line("!^").unwrap(), "one");
+        assert_eq!(expand_line("!*").unwrap(), "one two three");
+        assert_eq!(expand_line("!1:0-1").unwrap(), "ls -l");
+        assert_eq!(expand_line("!2:$").unwrap(), "'x y'");
+        assert_eq!(expand_line("!1:2:h").unwrap(), "/tmp");
+        assert_eq!(expand_line("!1:2:t:r").unwrap(), "file");
+        assert_eq!(expand_line("!!:gs/o/0/").unwrap(), "ech0 0ne tw0 three");
+
+        assert_eq!(expand_line("!1:5").unwrap_err(), "!1:5: bad word specifier");
+    }
+
+    #[test]
+    fn print_only_and_literal_bangs() {
+        let expansion = expand("!!:p", &history(), 1).unwrap();
+        assert_eq!(expansion.line, "echo one two three");
+        assert!(expansion.expanded && expansion.print_only);
+
+        let expansion = expand("echo '!!' \\!! hi!", &history(), 1).unwrap();
+        assert_eq!(expansion.line, "echo '!!' \\!! hi!");
+        assert!(!expansion.expanded);
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     collections::HashSet,
     io::{self, Write},
+    path::Path,
 };
 
 use crossterm::{
@@ -12,32 +13,69 @@ use crossterm::{
     execute,
     terminal::{self, ClearType},
 };
+use owo_colors::OwoColorize;
 
-pub fn retrieve_user_input(know_commands: &HashSet<String>, command_history: &[String]) -> String {
-    let prompt = "$ ";
-    print!("{prompt}");
+use crate::{
+    parser,
+    shell::{
+        self,
+        completion::{self, Candidate, CompletionRegistry},
+        highlight::Highlighter,
+        history::HistoryEntry,
+        menu::{self, MenuItem},
+        prompt::Prompt,
+    },
+};
+
+/// Above this many candidates, listing them needs confirmation (readline's default).
+const COMPLETION_QUERY_ITEMS: usize = 100;
+/// What submitted lines are left with when the `transient_prompt` option is on.
+const TRANSIENT_PROMPT: &str = "$ ";
+/// Columns kept free between the input and the right prompt before it is hidden.
+const RIGHT_PROMPT_GAP: usize = 2;
+
+pub fn retrieve_user_input(
+    prompt: &Prompt,
+    know_commands: &HashSet<String>,
+    command_history: &[HistoryEntry],
+    completions: &CompletionRegistry,
+    current_dir: &Path,
+) -> String {
+    print!("{}{}", prompt.header, prompt.line);
     io::stdout().flush().unwrap();
 
     terminal::enable_raw_mode().unwrap();
     execute!(io::stdout(), EnableBracketedPaste).unwrap();
 
+    let mut screen = Screen::new(Highlighter::new(know_commands, current_dir));
+    if !prompt.right.is_empty() {
+        screen.redraw(prompt, "", 0, "");
+    }
+
     let mut user_input = String::new(); // what the user has typed so far
-    let mut cursor_pos: usize = 0; // cursor position in the string
+    let mut cursor_pos: usize = 0; // cursor position in the string, in bytes
     let mut one_tab_pressed = false;
+    let mut list_shown = false;
+    let mut header_visible = true; // the prompt header is right above the input line
     let mut current_history_position = command_history.len();
 
     loop {
         let event = event::read().unwrap();
 
         if let Event::Paste(text) = &event {
-            for c in text.chars() {
-                if c == '\n' || c == '\r' {
-                    continue;
-                } // skip newlines in pasted text
-                user_input.insert(cursor_pos, c);
-                cursor_pos += 1;
-            }
-            redraw_line(prompt, &user_input, cursor_pos);
+            // pasted newlines are kept: the buffer becomes a multi-line input
+            let text = text.replace("\r\n", "\n").replace('\r', "\n");
+            user_input.insert_str(cursor_pos, &text);
+            cursor_pos += text.len();
+            let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+            screen.redraw(prompt, &user_input, cursor_pos, suggestion);
+            continue;
+        }
+
+        if let Event::Resize(columns, _) = event {
+            let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+            screen.resize(columns as usize, prompt, &user_input, cursor_pos, suggestion);
+            screen.redraw(prompt, &user_input, cursor_pos, suggestion);
             continue;
         }
 
@@ -48,19 +86,41 @@ pub fn retrieve_user_input(know_commands: &HashSet<String>, command_history: &[S
             ..
         }) = event
         {
+            let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+
             match code {
                 // In raw mode, \n (0x0A) is mapped to Ctrl+J instead of Enter
                 KeyCode::Char('j') if modifiers.contains(KeyModifiers::CONTROL) => {
-                    print!("\r\n");
+                    finish_line(&mut screen, prompt, &user_input, cursor_pos, header_visible);
                     break;
                 }
+                // Alt+F accepts the next word of the suggestion, or moves one word forward
+                KeyCode::Char('f') if modifiers.contains(KeyModifiers::ALT) => {
+                    if !suggestion.is_empty() {
+                        user_input.push_str(&suggestion[..next_word_len(suggestion)]);
+                        cursor_pos = user_input.len();
+                    } else {
+                        cursor_pos += next_word_len(&user_input[cursor_pos..]);
+                    }
+                    let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+                    screen.redraw(prompt, &user_input, cursor_pos, suggestion);
+                }
+                // in a multi-line input, Up and Down move between its lines before browsing history
+                KeyCode::Up if user_input[..cursor_pos].contains('\n') => {
+                    cursor_pos = line_above(&user_input, cursor_pos);
+                    screen.redraw(prompt, &user_input, cursor_pos, "");
+                }
+                KeyCode::Down if user_input[cursor_pos..].contains('\n') => {
+                    cursor_pos = line_below(&user_input, cursor_pos);
+                    screen.redraw(prompt, &user_input, cursor_pos, "");
+                }
                 KeyCode::Up => {
                     if !command_history.is_empty() && current_history_position > 0 {
                         current_history_position -= 1;
 
-                        user_input = command_history.get(current_history_position).unwrap().clone();
+                        user_input = command_history.get(current_history_position).unwrap().line.clone();
                         cursor_pos = user_input.len();
-                        redraw_line(prompt, &user_input, cursor_pos);
+                        screen.redraw(prompt, &user_input, cursor_pos, "");
                     }
                 }
                 KeyCode::Down => {
@@ -69,75 +129,123 @@ pub fn retrieve_user_input(know_commands: &HashSet<String>, command_history: &[S
                             current_history_position = command_history.len();
                             user_input = String::new();
                             cursor_pos = 0;
-                            redraw_line(prompt, &user_input, cursor_pos);
+                            screen.redraw(prompt, &user_input, cursor_pos, "");
                         } else {
                             current_history_position += 1;
 
-                            user_input = command_history.get(current_history_position).unwrap().clone();
+                            user_input = command_history.get(current_history_position).unwrap().line.clone();
                             cursor_pos = user_input.len();
-                            redraw_line(prompt, &user_input, cursor_pos);
+                            screen.redraw(prompt, &user_input, cursor_pos, "");
                         }
                     }
                 }
                 KeyCode::Char(c) => {
                     one_tab_pressed = false;
+                    list_shown = false;
                     user_input.insert(cursor_pos, c);
-                    cursor_pos += 1;
-                    redraw_line(prompt, &user_input, cursor_pos);
+                    cursor_pos += c.len_utf8();
+                    let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+                    screen.redraw(prompt, &user_input, cursor_pos, suggestion);
                 }
                 KeyCode::Left => {
-                    cursor_pos = cursor_pos.saturating_sub(1);
-                    redraw_line(prompt, &user_input, cursor_pos);
+                    cursor_pos = previous_char_start(&user_input, cursor_pos);
+                    screen.redraw(prompt, &user_input, cursor_pos, "");
                 }
-                KeyCode::Right => {
-                    if cursor_pos < user_input.len() {
-                        cursor_pos += 1;
+                // Right and End accept the whole suggestion when the cursor is at the end
+                KeyCode::Right | KeyCode::End => {
+                    if !suggestion.is_empty() {
+                        user_input.push_str(suggestion);
+                        cursor_pos = user_input.len();
+                    } else if code == KeyCode::End {
+                        cursor_pos = user_input.len();
+                    } else if let Some(c) = user_input[cursor_pos..].chars().next() {
+                        cursor_pos += c.len_utf8();
                     }
-                    redraw_line(prompt, &user_input, cursor_pos);
+                    let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+                    screen.redraw(prompt, &user_input, cursor_pos, suggestion);
                 }
                 KeyCode::Backspace => {
                     one_tab_pressed = false;
+                    list_shown = false;
                     if cursor_pos > 0 {
-                        cursor_pos -= 1;
+                        cursor_pos = previous_char_start(&user_input, cursor_pos);
                         user_input.remove(cursor_pos);
-                        redraw_line(prompt, &user_input, cursor_pos);
+                        let suggestion = find_suggestion(command_history, &user_input, cursor_pos);
+                        screen.redraw(prompt, &user_input, cursor_pos, suggestion);
                     }
                 }
                 KeyCode::Tab => {
-                    let found_commands: Vec<&String> = know_commands
-                        .iter()
-                        .filter(|i| i.starts_with(&user_input))
-                        .collect();
-
-                    if found_commands.is_empty() {
-                        redraw_line(prompt, &format!("{}\x07", user_input), cursor_pos); // beep!
-                    } else if found_commands.len() == 1 {
-                        let command_name = found_commands.first().unwrap();
-                        user_input = format!("{command_name} ");
-                        cursor_pos = user_input.len();
-                        redraw_line(prompt, &user_input, cursor_pos);
-                    } else {
-                        let mut names: Vec<&str> =
-                            found_commands.iter().map(|s| s.as_str()).collect();
-                        names.sort();
+                    let completion = completion::complete(
+                        &user_input,
+                        cursor_pos,
+                        know_commands,
+                        completions,
+                        current_dir,
+                    );
+                    let candidates: Vec<&Candidate> = completion.candidates.iter().collect();
 
-                        if !one_tab_pressed {
-                            one_tab_pressed = true;
+                    if candidates.is_empty() {
+                        screen.redraw(prompt, &user_input, cursor_pos, suggestion);
+                        beep();
+                    } else if candidates.len() == 1 {
+                        let replacement = completion.finish(candidates.first().unwrap());
+                        user_input.replace_range(completion.start..cursor_pos, &replacement);
+                        cursor_pos = completion.start + replacement.len();
+                        screen.redraw(prompt, &user_input, cursor_pos, "");
+                    } else if list_shown {
+                        // a third Tab opens the menu over the listed candidates
+                        list_shown = false;
+                        let items: Vec<MenuItem> = candidates
+                            .iter()
+                            .map(|c| MenuItem {
+                                label: c.display.clone(),
+                                description: c.description.clone(),
+                            })
+                            .collect();
 
-                            user_input = build_lcp(&names, &user_input);
-                            cursor_pos = user_input.len();
+                        let choice = menu::select(&items, |selected| {
+                            let replacement = &candidates[selected].replacement;
+                            let mut line = user_input.clone();
+                            line.replace_range(completion.start..cursor_pos, replacement);
+                            screen.redraw(prompt, &line, completion.start + replacement.len(), "");
+                        });
 
-                            redraw_line(prompt, &format!("{}\x07", user_input), cursor_pos); // beep!
-                        } else {
-                            one_tab_pressed = false;
-                            redraw_line(prompt, &user_input.to_string(), cursor_pos);
-                            print!("\r\n{}\r\n", names.join("  "));
-                            redraw_line(prompt, &user_input.to_string(), cursor_pos);
+                        if let Some(selected) = choice {
+                            let replacement = completion.finish(candidates[selected]);
+                            user_input.replace_range(completion.start..cursor_pos, &replacement);
+                            cursor_pos = completion.start + replacement.len();
                         }
+                        screen.redraw(prompt, &user_input, cursor_pos, "");
+                    } else if !one_tab_pressed {
+                        one_tab_pressed = true;
+
+                        let replacements: Vec<&str> =
+                            candidates.iter().map(|c| c.replacement.as_str()).collect();
+                        let lcp = build_lcp(&replacements, &completion.word);
+                        user_input.replace_range(completion.start..cursor_pos, &lcp);
+                        cursor_pos = completion.start + lcp.len();
+
+                        screen.redraw(prompt, &user_input, cursor_pos, "");
+                        beep();
+                    } else {
+                        one_tab_pressed = false;
+                        screen.redraw(prompt, &user_input.to_string(), cursor_pos, "");
+                        screen.leave();
+                        list_shown = show_candidates(&candidates);
+                        header_visible = false;
+                        screen.redraw(prompt, &user_input.to_string(), cursor_pos, suggestion);
                     }
                 }
+                // an unclosed quote or a trailing backslash continue on a new line
+                KeyCode::Enter if parser::is_incomplete(&user_input) => {
+                    one_tab_pressed = false;
+                    list_shown = false;
+                    user_input.push('\n');
+                    cursor_pos = user_input.len();
+                    screen.redraw(prompt, &user_input, cursor_pos, "");
+                }
                 KeyCode::Enter => {
-                    print!("\r\n");
+                    finish_line(&mut screen, prompt, &user_input, cursor_pos, header_visible);
                     break;
                 }
                 _ => {
@@ -153,48 +261,291 @@ pub fn retrieve_user_input(know_commands: &HashSet<String>, command_history: &[S
     user_input
 }
 
-fn build_lcp(names: &Vec<&str>, user_input: &str) -> String {
-    let mut lcp = names.first().unwrap().to_string();
-
-    for i in 1..(names.len() - 1) {
-        let word = names.get(i).unwrap();
+/// Lists the candidates in a grid sized to the terminal, asking first when there are many.
+/// Returns whether the list was shown.
+fn show_candidates(candidates: &[&Candidate]) -> bool {
+    if candidates.len() > COMPLETION_QUERY_ITEMS {
+        print!("\r\nDisplay all {} possibilities? (y or n)", candidates.len());
+        io::stdout().flush().unwrap();
 
-        let len = lcp.len().min(word.len());
-
-        if len == user_input.len() {
-            lcp = user_input.to_string();
-            break;
+        if !confirm() {
+            print!("\r\n");
+            return false;
         }
+    }
+
+    let items: Vec<MenuItem> = candidates
+        .iter()
+        .map(|c| MenuItem {
+            label: c.display.clone(),
+            description: c.description.clone(),
+        })
+        .collect();
+    let (width, _) = menu::terminal_size();
 
-        let mut last_equal = user_input.len();
+    print!("\r\n{}\r\n", menu::grid(&items, width, None).join("\r\n"));
+    true
+}
 
-        for j in user_input.len()..len {
-            if lcp.chars().nth(j) == word.chars().nth(j) {
-                last_equal += 1;
-            } else {
-                break;
+/// Waits for a y/n answer; space also means yes, as in readline.
+fn confirm() -> bool {
+    loop {
+        if let Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read().unwrap()
+        {
+            match code {
+                KeyCode::Char('y' | 'Y' | ' ') => return true,
+                KeyCode::Char('n' | 'N') | KeyCode::Esc | KeyCode::Backspace => return false,
+                _ => continue,
             }
         }
-        lcp = lcp[..last_equal].to_string();
+    }
+}
+
+fn build_lcp(names: &[&str], user_input: &str) -> String {
+    let mut lcp = names.first().unwrap().to_string();
+
+    for word in &names[1..] {
+        let common: usize = lcp
+            .chars()
+            .zip(word.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        lcp.truncate(common);
+    }
+
+    // never cut an escape sequence in half
+    let trailing_backslashes = lcp.len() - lcp.trim_end_matches('\\').len();
+    if trailing_backslashes % 2 == 1 {
+        lcp.pop();
+    }
+
+    if lcp.len() < user_input.len() || !lcp.starts_with(user_input) {
+        return user_input.to_string();
     }
 
     lcp
 }
 
-fn redraw_line(prompt: &str, input: &str, cursor_pos: usize) {
-    // print content
+/// Returns the part of the most recent history entry that extends the current input,
+/// shown as ghost text after the cursor. Empty when there is nothing to suggest.
+fn find_suggestion<'a>(
+    command_history: &'a [HistoryEntry],
+    user_input: &str,
+    cursor_pos: usize,
+) -> &'a str {
+    if user_input.is_empty() || cursor_pos < user_input.len() {
+        return "";
+    }
+
+    command_history
+        .iter()
+        .rev()
+        .map(|entry| &entry.line)
+        .find(|line| line.len() > user_input.len() && line.starts_with(user_input))
+        .map(|line| &line[user_input.len()..])
+        .unwrap_or_default()
+}
+
+/// Length of the leading whitespace plus the next word of `text`.
+/// Byte position of the character before `cursor_pos`, or 0 at the start.
+fn previous_char_start(input: &str, cursor_pos: usize) -> usize {
+    input[..cursor_pos].char_indices().next_back().map_or(0, |(index, _)| index)
+}
+
+fn next_word_len(text: &str) -> usize {
+    let spaces = text.len() - text.trim_start().len();
+    let word = text[spaces..].find(' ').unwrap_or(text.len() - spaces);
+    spaces + word
+}
+
+fn beep() {
+    print!("\x07");
+    io::stdout().flush().unwrap();
+}
+
+/// Leaves the submitted line on screen and moves to the next one. With the `transient_prompt`
+/// option the full prompt (header lines included, when still right above) is replaced by `$ `.
+fn finish_line(
+    screen: &mut Screen<'_>,
+    prompt: &Prompt,
+    input: &str,
+    cursor_pos: usize,
+    header_visible: bool,
+) {
+    if !shell::option_enabled("transient_prompt") {
+        screen.redraw(prompt, input, cursor_pos, "");
+        screen.leave();
+        print!("\r\n");
+        return;
+    }
+
     let mut stdout = io::stdout();
+    let header_lines = if header_visible {
+        prompt.header.matches('\n').count()
+    } else {
+        0
+    };
+    let rows_up = screen.cursor_row + header_lines;
+    if rows_up > 0 {
+        execute!(stdout, cursor::MoveUp(rows_up as u16)).unwrap();
+    }
 
     print!("\r");
-    execute!(stdout, terminal::Clear(ClearType::CurrentLine)).unwrap();
-    print!("{prompt}{input}");
+    execute!(stdout, terminal::Clear(ClearType::FromCursorDown)).unwrap();
+    print!("{TRANSIENT_PROMPT}{}\r\n", input.replace('\n', &format!("\r\n{}", prompt.continuation)));
+    stdout.flush().unwrap();
+}
 
-    // position cursos
-    print!("\r");
-    let target_col = prompt.len() + cursor_pos;
-    if target_col > 0 {
-        execute!(stdout, cursor::MoveRight(target_col as u16)).unwrap();
+/// Where the prompt line and its input were drawn, so a redraw can go back to their first row
+/// when the input wraps past the terminal width or spans several lines.
+struct Screen<'a> {
+    highlighter: Highlighter<'a>,
+    columns: usize,
+    /// Rows between the first row of the prompt line and the cursor.
+    cursor_row: usize,
+    /// Rows between the first row of the prompt line and the last drawn one.
+    end_row: usize,
+}
+
+impl<'a> Screen<'a> {
+    fn new(highlighter: Highlighter<'a>) -> Screen<'a> {
+        let (columns, _) = menu::terminal_size();
+        Screen {
+            highlighter,
+            columns: columns.max(1),
+            cursor_row: 0,
+            end_row: 0,
+        }
     }
 
-    stdout.flush().unwrap();
+    fn redraw(&mut self, prompt: &Prompt, input: &str, cursor_pos: usize, suggestion: &str) {
+        let mut stdout = io::stdout();
+
+        // go back to the first row and clear everything drawn from there
+        if self.cursor_row > 0 {
+            execute!(stdout, cursor::MoveUp(self.cursor_row as u16)).unwrap();
+        }
+        print!("\r");
+        execute!(stdout, terminal::Clear(ClearType::FromCursorDown)).unwrap();
+
+        print!("{}{}", prompt.line, self.highlighter.paint(input, &prompt.continuation));
+        if !suggestion.is_empty() {
+            let suggestion = suggestion.replace('\n', &format!("\r\n{}", prompt.continuation));
+            print!("{}", suggestion.dimmed());
+        }
+
+        let (cursor, end) = layout(self.columns, prompt, input, cursor_pos, suggestion);
+
+        // after filling the last column the terminal waits for one more character before
+        // wrapping; move to the next row now so the cursor is where the layout expects it
+        if end.1 == 0 && end.0 > 0 && !(suggestion.is_empty() && input.ends_with('\n')) {
+            print!(" \r");
+        }
+
+        // the right prompt steps aside when the input gets close to it
+        if !prompt.right.is_empty()
+            && end.0 == 0
+            && end.1 + RIGHT_PROMPT_GAP + prompt.right_width <= self.columns
+        {
+            execute!(stdout, cursor::MoveToColumn((self.columns - prompt.right_width) as u16)).unwrap();
+            print!("{}", prompt.right);
+        }
+
+        // position cursos
+        if end.0 > cursor.0 {
+            execute!(stdout, cursor::MoveUp((end.0 - cursor.0) as u16)).unwrap();
+        }
+        print!("\r");
+        if cursor.1 > 0 {
+            execute!(stdout, cursor::MoveRight(cursor.1 as u16)).unwrap();
+        }
+
+        self.cursor_row = cursor.0;
+        self.end_row = end.0;
+        stdout.flush().unwrap();
+    }
+
+    /// Moves the cursor to the last drawn row, so whatever is printed next goes below the input.
+    /// The next redraw starts a fresh prompt line where the cursor is.
+    fn leave(&mut self) {
+        if self.end_row > self.cursor_row {
+            execute!(io::stdout(), cursor::MoveDown((self.end_row - self.cursor_row) as u16)).unwrap();
+        }
+        self.cursor_row = 0;
+        self.end_row = 0;
+    }
+
+    /// Adopts a new terminal width. The terminal reflows what was drawn, so the cursor row
+    /// is recomputed for the new width before the next redraw.
+    fn resize(&mut self, columns: usize, prompt: &Prompt, input: &str, cursor_pos: usize, suggestion: &str) {
+        self.columns = columns.max(1);
+        let (cursor, end) = layout(self.columns, prompt, input, cursor_pos, suggestion);
+        self.cursor_row = cursor.0;
+        self.end_row = end.0;
+    }
+}
+
+/// Row and column of the cursor and of the end of the drawn text, counting from the first row
+/// of the prompt line. A row filled up to the last column puts the position on the next row.
+fn layout(
+    columns: usize,
+    prompt: &Prompt,
+    input: &str,
+    cursor_pos: usize,
+    suggestion: &str,
+) -> ((usize, usize), (usize, usize)) {
+    let wrap = |(row, column): (usize, usize)| (row + column / columns, column % columns);
+    let advance = |position: (usize, usize), character: char| match character {
+        '\n' => wrap((position.0 + 1, prompt.continuation_width)),
+        c if c.is_control() => position,
+        _ => wrap((position.0, position.1 + 1)),
+    };
+
+    let mut position = wrap((0, prompt.width));
+    let mut cursor = None;
+    for (index, character) in input.char_indices() {
+        if index == cursor_pos {
+            cursor = Some(position);
+        }
+        position = advance(position, character);
+    }
+    let cursor = cursor.unwrap_or(position);
+
+    for character in suggestion.chars() {
+        position = advance(position, character);
+    }
+
+    (cursor, position)
+}
+
+/// Cursor position on the previous line of a multi-line input, keeping the column when it can.
+fn line_above(input: &str, cursor_pos: usize) -> usize {
+    let line_start = input[..cursor_pos].rfind('\n').map_or(0, |p| p + 1);
+    let column = input[line_start..cursor_pos].chars().count();
+    let previous_start = input[..line_start - 1].rfind('\n').map_or(0, |p| p + 1);
+
+    column_position(input, previous_start, line_start - 1, column)
+}
+
+/// Cursor position on the next line of a multi-line input, keeping the column when it can.
+fn line_below(input: &str, cursor_pos: usize) -> usize {
+    let line_start = input[..cursor_pos].rfind('\n').map_or(0, |p| p + 1);
+    let column = input[line_start..cursor_pos].chars().count();
+    let next_start = cursor_pos + input[cursor_pos..].find('\n').unwrap_or_default() + 1;
+    let next_end = input[next_start..].find('\n').map_or(input.len(), |p| next_start + p);
+
+    column_position(input, next_start, next_end, column)
+}
+
+/// Byte position of `column` characters into the line `start..end`, or its end when shorter.
+fn column_position(input: &str, start: usize, end: usize, column: usize) -> usize {
+    input[start..end]
+        .char_indices()
+        .nth(column)
+        .map_or(end, |(offset, _)| start + offset)
 }
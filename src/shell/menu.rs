@@ -0,0 +1,153 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, ClearType},
+};
+use owo_colors::OwoColorize;
+
+pub struct MenuItem {
+    pub label: String,
+    pub description: Option<String>,
+}
+
+/// Terminal size as (columns, rows), with a sane fallback when it can't be queried.
+pub fn terminal_size() -> (usize, usize) {
+    terminal::size()
+        .map(|(columns, rows)| (columns as usize, rows as usize))
+        .unwrap_or((80, 24))
+}
+
+/// Number of rows used to lay the items out in columns that fit `width`.
+/// Items with descriptions are shown one per row, and a single row is used when everything fits.
+pub fn row_count(items: &[MenuItem], width: usize) -> usize {
+    if items.iter().any(|i| i.description.is_some()) {
+        return items.len();
+    }
+
+    let single_line: usize = items.iter().map(|i| i.label.chars().count() + 2).sum();
+    if single_line <= width + 2 {
+        return 1;
+    }
+
+    let column_width = label_width(items) + 2;
+    let columns = (width / column_width).max(1);
+    items.len().div_ceil(columns)
+}
+
+/// Renders the items column by column (like `ls`), highlighting `selected` if any.
+pub fn grid(items: &[MenuItem], width: usize, selected: Option<usize>) -> Vec<String> {
+    let rows = row_count(items, width).max(1);
+    let label_width = if rows == 1 { 0 } else { label_width(items) };
+    let mut lines = vec![String::new(); rows];
+
+    for (position, item) in items.iter().enumerate() {
+        let padding = label_width.saturating_sub(item.label.chars().count());
+        let mut cell = match &item.description {
+            Some(description) => format!("{}{}  -- {description}", item.label, " ".repeat(padding)),
+            None => format!("{}{}", item.label, " ".repeat(padding)),
+        };
+
+        if Some(position) == selected {
+            cell = cell.reversed().to_string();
+        }
+
+        let line = &mut lines[position % rows];
+        if !line.is_empty() {
+            line.push_str("  ");
+        }
+        line.push_str(&cell);
+    }
+
+    lines
+        .into_iter()
+        .map(|line| line.trim_end().to_string())
+        .collect()
+}
+
+/// Lets the user pick one of the items with Tab/arrows, drawn below the current line.
+/// `redraw_anchor` repaints the line above the menu for the highlighted item.
+/// Returns the chosen position on Enter, or `None` when cancelled with Esc or Ctrl+C.
+pub fn select(items: &[MenuItem], mut redraw_anchor: impl FnMut(usize)) -> Option<usize> {
+    if items.is_empty() {
+        return None;
+    }
+
+    let mut selected = 0;
+    let mut offset = 0;
+
+    let choice = loop {
+        let (width, height) = terminal_size();
+        let rows = row_count(items, width);
+        let visible = rows.min(height.saturating_sub(2).max(1));
+
+        // keep the highlighted row inside the visible window
+        let selected_row = selected % rows;
+        if selected_row < offset {
+            offset = selected_row;
+        } else if selected_row >= offset + visible {
+            offset = selected_row + 1 - visible;
+        }
+
+        // the anchor first: repainting it may clear everything below it
+        redraw_anchor(selected);
+        draw(&grid(items, width, Some(selected))[offset..offset + visible]);
+
+        let Ok(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            modifiers,
+            ..
+        })) = event::read()
+        else {
+            continue;
+        };
+
+        match code {
+            KeyCode::Enter => break Some(selected),
+            KeyCode::Esc => break None,
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break None,
+            KeyCode::Tab | KeyCode::Down => selected = (selected + 1) % items.len(),
+            KeyCode::BackTab | KeyCode::Up => selected = (selected + items.len() - 1) % items.len(),
+            KeyCode::Right if selected + rows < items.len() => selected += rows,
+            KeyCode::Left if selected >= rows => selected -= rows,
+            _ => continue,
+        }
+    };
+
+    clear_below();
+    choice
+}
+
+/// Prints the lines below the current one and moves the cursor back up.
+fn draw(lines: &[String]) {
+    let mut stdout = io::stdout();
+
+    clear_below();
+    print!("\r\n{}", lines.join("\r\n"));
+    execute!(stdout, cursor::MoveUp(lines.len() as u16)).unwrap();
+    stdout.flush().unwrap();
+}
+
+fn clear_below() {
+    let mut stdout = io::stdout();
+
+    execute!(
+        stdout,
+        cursor::SavePosition,
+        cursor::MoveToNextLine(1),
+        terminal::Clear(ClearType::FromCursorDown),
+        cursor::RestorePosition
+    )
+    .unwrap();
+}
+
+fn label_width(items: &[MenuItem]) -> usize {
+    items
+        .iter()
+        .map(|i| i.label.chars().count())
+        .max()
+        .unwrap_or_default()
+}
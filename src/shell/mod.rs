@@ -1,26 +1,56 @@
+pub mod command_hash;
+pub mod completion;
+pub mod frecency;
+pub mod highlight;
+pub mod history;
+pub mod history_expansion;
 pub mod input;
+pub mod menu;
+pub mod prompt;
 pub mod output;
 
-use std::{collections::HashSet, path::{Path, PathBuf}};
+use std::{collections::HashSet, env, path::{Path, PathBuf}};
 
-use crate::os::OSInstance;
+use crate::{
+    os::OSInstance,
+    shell::{command_hash::CommandHash, completion::CompletionRegistry, history::History},
+};
+
+/// Whether an opt-in shell option is listed in the colon-separated `SHELL_OPTIONS` variable,
+/// e.g. `SHELL_OPTIONS=fuzzy_complete`.
+pub fn option_enabled(name: &str) -> bool {
+    env::var("SHELL_OPTIONS").is_ok_and(|options| options.split(':').any(|o| o == name))
+}
 
 pub struct CommandInput<'a> {
     pub command_name: &'a str,
     pub command_arguments: &'a [String],
     pub current_dir: &'a Path,
     pub os: &'a OSInstance,
-    pub command_history: &'a Vec<String>,
+    pub command_history: &'a History,
     pub shell_commands: &'a HashSet<String>,
+    pub completions: &'a CompletionRegistry,
+    /// `pushd` stack, without the current directory.
+    pub dir_stack: &'a [PathBuf],
+    pub command_hash: &'a CommandHash,
     pub std_input: Option<String>,
+    /// Whether the output goes straight to the terminal: the last command of the pipeline,
+    /// not redirected, with stdout a TTY.
+    pub terminal_output: bool,
 }
 
 #[derive(Default)]
 pub struct CommandOutput {
     pub updated_dir: Option<PathBuf>,
-    pub command_history: Option<Vec<String>>,
+    pub command_history: Option<History>,
+    pub completions: Option<CompletionRegistry>,
+    pub dir_stack: Option<Vec<PathBuf>>,
+    pub command_hash: Option<CommandHash>,
     pub std_output: Option<String>,
     pub std_error: Option<String>,
+    /// Exit status when it isn't just whether there is an error message, e.g. a lookup that
+    /// fails without printing anything.
+    pub status: Option<i32>,
 }
 
 impl CommandOutput {
@@ -49,10 +79,17 @@ impl CommandOutput {
         }
     }
 
-    pub fn history_update(history: Vec<String>) -> Self {
+    pub fn history_update(history: History) -> Self {
         Self {
             command_history: Some(history),
             ..Default::default()
         }
     }
+
+    pub fn completions_update(completions: CompletionRegistry) -> Self {
+        Self {
+            completions: Some(completions),
+            ..Default::default()
+        }
+    }
 }
@@ -4,47 +4,33 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{OutputProcessor, parser};
+use crate::{
+    OutputProcessor,
+    parser::{self, TokenKind},
+};
 
 pub fn define_output_processor(command: &str, current_dir: &Path) -> Result<OutputProcessor, String> {
-    let find_position = command.find('>');
+    let tokens = parser::tokenize(command);
+    let Some(position) = tokens.iter().position(|t| t.kind == TokenKind::Redirect) else {
+        return Ok(OutputProcessor::Console);
+    };
 
-    if let Some(position) = find_position {
-        // chgeck byte after the '>' position to see if it is an append operation, with another '>'
-        let append_operation = if let Some(&byte_value) = position
-            .checked_add(1)
-            .and_then(|i| command.as_bytes().get(i))
-        {
-            byte_value == b'>'
-        } else {
-            false
-        };
+    // input redirection isn't supported, the command reads nothing
+    let operator = &tokens[position].value;
+    if operator.ends_with('<') {
+        return Ok(OutputProcessor::Console);
+    }
 
-        let inital_argument_position = if append_operation {
-            position + 2
-        } else {
-            position + 1
-        };
+    let target = match tokens.get(position + 1) {
+        Some(token) if token.kind == TokenKind::Word => token.value.as_str(),
+        _ => return Err(String::from("Invalid redirect output operation: missing file name")),
+    };
+    let append_operation = operator.ends_with(">>");
 
-        match parser::parse_path(command[inital_argument_position..].trim(), current_dir) {
-            Ok(path) => {
-                if let Some(&byte_value) = position
-                    .checked_sub(1)
-                    .and_then(|i| command.as_bytes().get(i))
-                {
-                    if byte_value == b'2' {
-                        Ok(OutputProcessor::StderrToFile(path, append_operation))
-                    } else {
-                        Ok(OutputProcessor::StdoutToFile(path, append_operation))
-                    }
-                } else {
-                    Ok(OutputProcessor::StdoutToFile(path, append_operation))
-                }
-            }
-            Err(message) => Err(format!("Invalid redirect output operation: {message}")),
-        }
-    } else {
-        Ok(OutputProcessor::Console)
+    match parser::parse_path(target, current_dir) {
+        Ok(path) if operator.starts_with('2') => Ok(OutputProcessor::StderrToFile(path, append_operation)),
+        Ok(path) => Ok(OutputProcessor::StdoutToFile(path, append_operation)),
+        Err(message) => Err(format!("Invalid redirect output operation: {message}")),
     }
 }
 
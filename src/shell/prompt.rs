@@ -0,0 +1,267 @@
+use std::{
+    env,
+    path::Path,
+    time::Duration,
+};
+
+use crate::{git::GitCache, os};
+
+const DEFAULT_PS1: &str = "$ ";
+const DEFAULT_PS2: &str = "> ";
+
+/// Prompt printed before the editable line.
+pub struct Prompt {
+    /// Lines printed once above the editable line (everything up to the last newline).
+    pub header: String,
+    /// Last line of the prompt, repainted on every redraw.
+    pub line: String,
+    /// Printable width of `line`, without escape sequences.
+    pub width: usize,
+    /// Right-aligned prompt from `RPS1`, drawn on the input line while there is room for it.
+    pub right: String,
+    /// Printable width of `right`.
+    pub right_width: usize,
+    /// Prompt from `PS2` shown before each continuation line of a multi-line input.
+    pub continuation: String,
+    /// Printable width of `continuation`.
+    pub continuation_width: usize,
+}
+
+/// Shell state the prompt escapes can refer to.
+pub struct PromptContext<'a> {
+    pub current_dir: &'a Path,
+    pub last_status: i32,
+    /// How long the last command line took to run, `None` before the first one.
+    pub last_duration: Option<Duration>,
+    pub history_number: usize,
+    /// Repository state for `\g`, only read when the prompt uses it.
+    pub git_cache: &'a mut GitCache,
+}
+
+/// Builds the prompt from `PS1`, or `$ ` when it isn't set, the right prompt from `RPS1`
+/// and the continuation prompt from `PS2` (`> ` by default).
+pub fn build(context: &mut PromptContext) -> Prompt {
+    let ps1 = env::var("PS1").unwrap_or_else(|_| DEFAULT_PS1.to_string());
+    let mut prompt = render(&ps1, context);
+
+    let ps2 = env::var("PS2").unwrap_or_else(|_| DEFAULT_PS2.to_string());
+    let continuation = render(&ps2, context);
+    prompt.continuation = continuation.line;
+    prompt.continuation_width = continuation.width;
+
+    if let Ok(rps1) = env::var("RPS1")
+        && !rps1.is_empty()
+    {
+        // the right prompt shares the input line, so only its last line is kept
+        let right = render(&rps1, context);
+        prompt.right = right.line;
+        prompt.right_width = right.width;
+    }
+
+    prompt
+}
+
+/// Expands bash-style prompt escapes. Text between `\[` and `\]` (and any ANSI escape sequence)
+/// is left out of the width so the cursor can be placed after the prompt.
+pub fn render(template: &str, context: &mut PromptContext) -> Prompt {
+    let mut text = String::new();
+    let mut width = 0;
+    let mut non_printing = false;
+    let mut characters = template.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        let expansion = match character {
+            '\\' => {
+                let Some(escape) = characters.next() else {
+                    text.push('\\');
+                    width += 1;
+                    break;
+                };
+
+                match escape {
+                    '[' => {
+                        non_printing = true;
+                        continue;
+                    }
+                    ']' => {
+                        non_printing = false;
+                        continue;
+                    }
+                    'D' if characters.peek() == Some(&'{') => {
+                        characters.next();
+                        let format: String = characters.by_ref().take_while(|&c| c != '}').collect();
+                        let format = if format.is_empty() { "%X" } else { &format };
+                        os::format_time(os::now(), format)
+                    }
+                    '0'..='7' => {
+                        let mut octal = escape.to_string();
+                        while octal.len() < 3
+                            && let Some(&digit @ '0'..='7') = characters.peek()
+                        {
+                            octal.push(digit);
+                            characters.next();
+                        }
+                        let value = u8::from_str_radix(&octal, 8).unwrap_or_default();
+                        (value as char).to_string()
+                    }
+                    other => expand_escape(other, context),
+                }
+            }
+            '$' => match characters.peek() {
+                Some('?') => {
+                    characters.next();
+                    context.last_status.to_string()
+                }
+                Some('{') => {
+                    characters.next();
+                    let name: String = characters.by_ref().take_while(|&c| c != '}').collect();
+                    env::var(name).unwrap_or_default()
+                }
+                Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                    let mut name = String::new();
+                    while let Some(&c) = characters.peek()
+                        && (c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        name.push(c);
+                        characters.next();
+                    }
+                    env::var(name).unwrap_or_default()
+                }
+                _ => String::from("$"),
+            },
+            other => other.to_string(),
+        };
+
+        text.push_str(&expansion);
+        if !non_printing {
+            // only the last line counts, it is the one the input follows
+            match expansion.rfind('\n') {
+                Some(position) => width = visible_width(&expansion[position + 1..]),
+                None => width += visible_width(&expansion),
+            }
+        }
+    }
+
+    let (header, line) = match text.rfind('\n') {
+        Some(position) => (text[..=position].to_string(), text[position + 1..].to_string()),
+        None => (String::new(), text),
+    };
+
+    Prompt {
+        header,
+        line,
+        width,
+        right: String::new(),
+        right_width: 0,
+        continuation: String::new(),
+        continuation_width: 0,
+    }
+}
+
+fn expand_escape(escape: char, context: &mut PromptContext) -> String {
+    match escape {
+        'a' => String::from("\x07"),
+        'e' => String::from("\x1b"),
+        'n' => String::from("\n"),
+        'r' => String::from("\r"),
+        '\\' => String::from("\\"),
+        'u' => env::var("USER")
+            .ok()
+            .or_else(|| os::user_name(os::current_uid()))
+            .unwrap_or_default(),
+        'h' => os::host_name().split('.').next().unwrap_or_default().to_string(),
+        'H' => os::host_name(),
+        'w' => abbreviate_home(context.current_dir),
+        'W' => match context.current_dir.file_name() {
+            _ if is_home(context.current_dir) => String::from("~"),
+            Some(name) => name.to_string_lossy().to_string(),
+            None => context.current_dir.display().to_string(),
+        },
+        'd' => os::format_time(os::now(), "%a %b %d"),
+        't' => os::format_time(os::now(), "%H:%M:%S"),
+        'T' => os::format_time(os::now(), "%I:%M:%S"),
+        '@' => os::format_time(os::now(), "%I:%M %p"),
+        'A' => os::format_time(os::now(), "%H:%M"),
+        '?' => context.last_status.to_string(),
+        'c' => context.last_duration.map(format_duration).unwrap_or_default(),
+        // there is no job control, so there are never any jobs
+        'j' => String::from("0"),
+        '!' => context.history_number.to_string(),
+        's' => String::from(env!("CARGO_PKG_NAME")),
+        'v' | 'V' => String::from(env!("CARGO_PKG_VERSION")),
+        '$' => String::from(if os::current_uid() == 0 { "#" } else { "$" }),
+        'g' => match context.git_cache.summary(context.current_dir) {
+            Some(summary) => format!(" ({})", summary.describe()),
+            None => String::new(),
+        },
+        other => format!("\\{other}"),
+    }
+}
+
+/// `\w`: the directory with `$HOME` shown as `~`.
+pub fn abbreviate_home(dir: &Path) -> String {
+    if let Ok(home) = env::var("HOME")
+        && !home.is_empty()
+        && let Ok(rest) = dir.strip_prefix(&home)
+    {
+        if rest.as_os_str().is_empty() {
+            return String::from("~");
+        }
+        return format!("~/{}", rest.display());
+    }
+
+    dir.display().to_string()
+}
+
+fn is_home(dir: &Path) -> bool {
+    env::var("HOME").is_ok_and(|home| !home.is_empty() && dir == Path::new(&home))
+}
+
+/// `\c`: a short human form of a command duration, e.g. `850ms`, `4.2s`, `3m05s`, `1h02m`.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    if seconds == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if seconds < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if seconds < 3600 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+/// Number of terminal columns `text` takes, skipping ANSI escape sequences and control characters.
+pub fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut characters = text.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character == '\x1b' {
+            match characters.next() {
+                // CSI sequences end with a byte in the '@'..='~' range
+                Some('[') => {
+                    for c in characters.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC sequences (e.g. window titles) end with BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = characters.next() {
+                        if c == '\x07' || (c == '\x1b' && characters.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        } else if !character.is_control() {
+            width += 1;
+        }
+    }
+
+    width
+}